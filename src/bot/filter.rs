@@ -0,0 +1,125 @@
+use discord::model::{
+    ChannelId,
+    User,
+    RoleId,
+    Event,
+    Channel,
+};
+
+use config::{ServerConfig, FilterConfig};
+use bot::Bot;
+use bot::server::Server;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventCategory {
+    Message,
+    Member,
+    Role,
+    Ban,
+    Emoji,
+    Channel,
+    Reaction,
+}
+
+impl EventCategory {
+    fn overrides(self, config: &ServerConfig) -> Option<&FilterConfig> {
+        match self {
+            EventCategory::Message => config.message_filter.as_ref(),
+            EventCategory::Member => config.member_filter.as_ref(),
+            EventCategory::Role => config.role_filter.as_ref(),
+            EventCategory::Ban => config.ban_filter.as_ref(),
+            EventCategory::Emoji => config.emoji_filter.as_ref(),
+            EventCategory::Channel => config.channel_filter.as_ref(),
+            EventCategory::Reaction => config.reaction_filter.as_ref(),
+        }
+    }
+}
+
+impl Server {
+    /// Returns true if an event of the given category, which happened in the given channel
+    /// and / or was caused by the given user, must not be logged.
+    pub fn is_ignored(&self, category: EventCategory, channel_id: Option<ChannelId>, user: Option<&User>) -> bool {
+        let config = match self.config {
+            Some(ref config) => config,
+            None => return false,
+        };
+        let overrides = category.overrides(config);
+        let ignored_channels = overrides.and_then(|f| f.ignored_channels.as_ref())
+            .or(config.ignored_channels.as_ref());
+        let ignored_users = overrides.and_then(|f| f.ignored_users.as_ref())
+            .or(config.ignored_users.as_ref());
+        let ignored_roles = overrides.and_then(|f| f.ignored_roles.as_ref())
+            .or(config.ignored_roles.as_ref());
+        let ignore_bots = overrides.and_then(|f| f.ignore_bots)
+            .or(config.ignore_bots)
+            .unwrap_or(false);
+
+        if let (Some(channel_id), Some(ignored)) = (channel_id, ignored_channels) {
+            if ignored.contains(&channel_id.0) {
+                return true;
+            }
+        }
+        let user = match user {
+            Some(user) => user,
+            None => return false,
+        };
+        if ignore_bots && user.bot {
+            return true;
+        }
+        if let Some(ignored) = ignored_users {
+            if ignored.contains(&user.id.0) {
+                return true;
+            }
+        }
+        if let (Some(member), Some(ignored)) = (self.members.get(&user.id), ignored_roles) {
+            if member.roles.iter().any(|&RoleId(id)| ignored.contains(&id)) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Bot {
+    /// Returns true if nothing must be logged for the event.
+    ///
+    /// This is checked before the event is handled, so authors of deleted messages and
+    /// removed members are still known from the cache.
+    pub fn is_event_ignored(&self, evt: &Event) -> bool {
+        let server = match self.event_server(evt).and_then(|id| self.servers.get(&id)) {
+            Some(server) => server,
+            None => return false,
+        };
+        let (category, channel_id, user) = match *evt {
+            Event::MessageCreate(ref msg) => (EventCategory::Message, Some(msg.channel_id), Some(&msg.author)),
+            Event::MessageUpdate(ref update) => {
+                let author = server.messages.get(&update.id).map(|msg| &msg.author).or(update.author.as_ref());
+                (EventCategory::Message, Some(update.channel_id), author)
+            },
+            Event::MessageDelete(ref del) => {
+                let author = server.messages.get(&del.message_id).map(|msg| &msg.author);
+                (EventCategory::Message, Some(del.channel_id), author)
+            },
+            // authors of single messages are filtered when the deleted messages are listed
+            Event::MessageDeleteBulk(ref del) => (EventCategory::Message, Some(del.channel_id), None),
+            Event::ChannelPinsAck(ref ack) => (EventCategory::Message, Some(ack.channel_id), None),
+            Event::ChannelPinsUpdate(ref update) => (EventCategory::Message, Some(update.channel_id), None),
+            Event::ReactionAdd(ref reaction) | Event::ReactionRemove(ref reaction) => {
+                let user = server.members.get(&reaction.user_id).map(|member| &member.user);
+                (EventCategory::Reaction, Some(reaction.channel_id), user)
+            },
+            Event::ServerMemberAdd(_, ref member) => (EventCategory::Member, None, Some(&member.user)),
+            Event::ServerMemberUpdate(ref update) => (EventCategory::Member, None, Some(&update.user)),
+            Event::ServerMemberRemove(_, ref user) => (EventCategory::Member, None, Some(user)),
+            Event::ServerRoleCreate(..) | Event::ServerRoleUpdate(..) | Event::ServerRoleDelete(..) =>
+                (EventCategory::Role, None, None),
+            Event::ServerBanAdd(_, ref user) | Event::ServerBanRemove(_, ref user) => (EventCategory::Ban, None, Some(user)),
+            Event::ServerEmojisUpdate(..) => (EventCategory::Emoji, None, None),
+            Event::ChannelCreate(Channel::Public(ref channel))
+                | Event::ChannelUpdate(Channel::Public(ref channel))
+                | Event::ChannelDelete(Channel::Public(ref channel)) => (EventCategory::Channel, Some(channel.id), None),
+            _ => return false,
+        };
+        server.is_ignored(category, channel_id, user)
+    }
+}
@@ -1,10 +1,17 @@
 use std::collections::HashMap;
 
 use bot::Bot;
+use bot::server::Server;
+use bot::permissions::permission_names;
 use modelext::{MergeIntoMap, Diff, ChannelUpdateDiff};
 
 use discord::model::{
     Channel,
+    ChannelId,
+    PublicChannel,
+    RoleId,
+    UserId,
+    Permissions,
 };
 
 use errors::*;
@@ -35,24 +42,41 @@ impl Bot {
             assert!(false, "handle_channel_update: expected public channel: {:?}", channel);
         }
         let channel = new.unwrap();
+        let old;
         let diffs;
         {
             let server = self.server_by_server_mut(channel.server_id)?;
-            let old = unwrap!(server.channels.insert(channel.id, channel.clone()));
+            old = unwrap!(server.channels.insert(channel.id, channel.clone()));
             diffs = old.diff(&channel)?;
         }
+        if diffs.iter().any(|diff| match *diff { ChannelUpdateDiff::Name(..) => true, _ => false }) {
+            self.channel_renamed(channel.server_id, channel.id);
+        }
         let server = self.server_by_server(channel.server_id)?;
         let mut map = HashMap::new();
         map.insert("channel_id".to_string(), channel.id.to_string());
-        map.insert("channel_name".to_string(), channel.name);
+        map.insert("channel_name".to_string(), channel.name.clone());
         if diffs.is_empty() {
             let template = server.config.as_ref().and_then(|c| c.channel_update_no_change_msg.as_ref());
             self.log_fmt(server.log_channel, template, &map)?;
             return Ok(());
         }
+        let mut roles = Vec::new();
+        let mut users = Vec::new();
+        for diff in &diffs {
+            match *diff {
+                ChannelUpdateDiff::RolePermission(id, ..) if !roles.contains(&id) => roles.push(id),
+                ChannelUpdateDiff::UserPermission(id, ..) if !users.contains(&id) => users.push(id),
+                _ => {}
+            }
+        }
         for diff in diffs {
             let fmt = match diff {
                 ChannelUpdateDiff::Name(..) => server.config.as_ref().and_then(|c| c.channel_update_name_msg.as_ref()),
+                ChannelUpdateDiff::UserOverwriteAdded(..) => server.config.as_ref().and_then(|c| c.channel_update_user_overwrite_add_msg.as_ref()),
+                ChannelUpdateDiff::UserOverwriteRemoved(..) => server.config.as_ref().and_then(|c| c.channel_update_user_overwrite_remove_msg.as_ref()),
+                ChannelUpdateDiff::RoleOverwriteAdded(..) => server.config.as_ref().and_then(|c| c.channel_update_role_overwrite_add_msg.as_ref()),
+                ChannelUpdateDiff::RoleOverwriteRemoved(..) => server.config.as_ref().and_then(|c| c.channel_update_role_overwrite_remove_msg.as_ref()),
                 ChannelUpdateDiff::UserPermission(..) => server.config.as_ref().and_then(|c| c.channel_update_user_perms_msg.as_ref()),
                 ChannelUpdateDiff::RolePermission(..) => server.config.as_ref().and_then(|c| c.channel_update_role_perms_msg.as_ref()),
                 ChannelUpdateDiff::Topic(..) => server.config.as_ref().and_then(|c| c.channel_update_topic_msg.as_ref()),
@@ -64,6 +88,61 @@ impl Bot {
             diff.merge_into_map(&mut map)?;
             self.log_fmt(server.log_channel, fmt, &map)?;
         }
+        self.log_permission_changes(server, &old, &channel, &roles, &users, &map)
+    }
+
+    /// Logs which effective permissions the given roles and users gained or lost
+    /// by the changed overwrites of the channel.
+    ///
+    /// Roles are looked at as if they were the only role of a member besides `@everyone`.
+    fn log_permission_changes(&self, server: &Server, old: &PublicChannel, new: &PublicChannel,
+            roles: &[RoleId], users: &[UserId], map: &HashMap<String, String>) -> Result<()> {
+        let config = match server.config {
+            Some(ref config) => config,
+            None => return Ok(()),
+        };
+        if config.channel_update_role_gained_msg.is_none() && config.channel_update_role_lost_msg.is_none()
+                && config.channel_update_user_gained_msg.is_none() && config.channel_update_user_lost_msg.is_none() {
+            return Ok(());
+        }
+        for &role_id in roles {
+            let (from, to) = match (server.role_permissions(role_id, old), server.role_permissions(role_id, new)) {
+                (Some(from), Some(to)) => (from, to),
+                // overwrites of deleted roles aren't removed by discord
+                _ => continue,
+            };
+            let mut map = map.clone();
+            map.insert("role_id".to_string(), role_id.to_string());
+            map.insert("role_name".to_string(), server.roles[&role_id].name.clone());
+            self.log_gained_lost(server.log_channel, config.channel_update_role_gained_msg.as_ref(),
+                config.channel_update_role_lost_msg.as_ref(), from, to, map)?;
+        }
+        for &user_id in users {
+            let (from, to) = match (server.member_permissions(user_id, old), server.member_permissions(user_id, new)) {
+                (Some(from), Some(to)) => (from, to),
+                // the member might have left already
+                _ => continue,
+            };
+            let mut map = map.clone();
+            server.members[&user_id].clone().merge_into_map_prefix(&mut map, "user_")?;
+            self.log_gained_lost(server.log_channel, config.channel_update_user_gained_msg.as_ref(),
+                config.channel_update_user_lost_msg.as_ref(), from, to, map)?;
+        }
+        Ok(())
+    }
+
+    fn log_gained_lost(&self, log_channel: Option<ChannelId>, gained_fmt: Option<&String>, lost_fmt: Option<&String>,
+            from: Permissions, to: Permissions, mut map: HashMap<String, String>) -> Result<()> {
+        let gained = Permissions::from_bits_truncate(to.bits() & !from.bits());
+        let lost = Permissions::from_bits_truncate(from.bits() & !to.bits());
+        if !gained.is_empty() {
+            map.insert("permissions".to_string(), permission_names(gained));
+            self.log_fmt(log_channel, gained_fmt, &map)?;
+        }
+        if !lost.is_empty() {
+            map.insert("permissions".to_string(), permission_names(lost));
+            self.log_fmt(log_channel, lost_fmt, &map)?;
+        }
         Ok(())
     }
 
@@ -85,4 +164,4 @@ impl Bot {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 
 use bot::Bot;
+use bot::filter::EventCategory;
 use modelext::{MergeIntoMap, Diff, MessageUpdateDiff};
+use textdiff;
 
 use discord::model::{
     Message,
@@ -71,6 +73,16 @@ impl Bot {
                 diff.apply(message)?;
             }
             let server = self.server_by_channel(update.channel_id)?;
+            // large edits are attached as diff file instead of being rendered inline,
+            // unless there is no template for them
+            let threshold = server.config.as_ref().and_then(|c| c.message_update_content_diff_threshold);
+            let diff_fmt = server.config.as_ref().and_then(|c| c.message_update_content_diff_msg.as_ref());
+            let attachment = match diff {
+                MessageUpdateDiff::Content(ref from, ref to) if diff_fmt.is_some()
+                        && threshold.map(|t| from.len() > t || to.len() > t).unwrap_or(false) =>
+                    Some(textdiff::unified(from, to)),
+                _ => None,
+            };
             let fmt = match diff {
                 MessageUpdateDiff::Kind(..) => server.config.as_ref().and_then(|c| c.message_update_kind_msg.as_ref()),
                 MessageUpdateDiff::Content(..) => server.config.as_ref().and_then(|c| c.message_update_content_msg.as_ref()),
@@ -91,7 +103,11 @@ impl Bot {
             };
             let mut map = map.clone();
             diff.merge_into_map(&mut map)?;
-            self.log_fmt(log_channel, fmt, &map)?;
+            if let Some(attachment) = attachment {
+                self.log_fmt_file(log_channel, diff_fmt, &map, "edit.diff", attachment)?;
+            } else {
+                self.log_fmt(log_channel, fmt, &map)?;
+            }
         }
         Ok(())
     }
@@ -124,7 +140,6 @@ impl Bot {
             let template = server.config.as_ref().and_then(|c| c.message_delete_bulk_msg.as_ref());
             self.log_fmt(server.log_channel, template, &map)?;
         }
-        let mut text = String::new();
         for message_id in del.ids {
             let cached;
             {
@@ -133,6 +148,7 @@ impl Bot {
             }
             let server = self.server_by_channel(del.channel_id)?;
             let line = match cached {
+                Some(ref msg) if server.is_ignored(EventCategory::Message, Some(del.channel_id), Some(&msg.author)) => None,
                 Some(msg) => server.config.as_ref().and_then(|c| c.message_delete_cached_msg.as_ref())
                         .map(|fmt| msg.into_map().and_then(|map| strfmt(fmt, &map)
                             .map_err(|err| ErrorKind::FormatError(server.id, err).into()))),
@@ -146,20 +162,11 @@ impl Bot {
                             .map_err(|err| ErrorKind::FormatError(server.id, err).into())))
                 }
             };
+            // consecutive lines are coalesced by the log queue
             if let Some(line) = line {
-                let line = line?;
-                if text.len() + line.len() > 2000 {
-                    let server = self.server_by_channel(del.channel_id)?;
-                    self.log(server.log_channel, &text)?;
-                    text = line;
-                } else {
-                    text += "\n";
-                    text += &line;
-                }
+                self.log_event(server.log_channel, &line?)?;
             }
         }
-        let server = self.server_by_channel(del.channel_id)?;
-        self.log(server.log_channel, &text)?;
         Ok(())
     }
 }
\ No newline at end of file
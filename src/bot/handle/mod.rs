@@ -6,15 +6,26 @@ mod emoji;
 mod channel;
 mod pins;
 mod reaction;
+mod server;
 
 use bot::Bot;
 
-use discord::model::{Event, PossibleServer};
+use discord::model::{Event, PossibleServer, Channel, ServerId};
 
 use errors::*;
 
 impl Bot {
+    /// Handles an event of a server.
+    ///
+    /// The state is always updated, but nothing is logged for events which are filtered.
     pub fn handle_event(&mut self, evt: Event) -> Result<()> {
+        self.ignore_event = self.is_event_ignored(&evt);
+        let res = self.dispatch_event(evt);
+        self.ignore_event = false;
+        res
+    }
+
+    fn dispatch_event(&mut self, evt: Event) -> Result<()> {
         match evt {
             // Event::Ready
             // Event::Resumed
@@ -53,8 +64,9 @@ impl Bot {
                     _ => {}
                 }
             },
-            // TODO: update server
-            // Event::ServerUpdate
+            Event::ServerUpdate(server) => {
+                self.handle_server_update(server)?;
+            },
             // TODO: handle offline servers
             // Event::ServerDelete
             Event::ServerMemberAdd(server_id, member) => {
@@ -112,4 +124,35 @@ impl Bot {
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Returns the server an event belongs to, if any.
+    pub fn event_server(&self, evt: &Event) -> Option<ServerId> {
+        let channel_id = match *evt {
+            Event::MessageCreate(ref msg) => msg.channel_id,
+            Event::MessageUpdate(ref update) => update.channel_id,
+            Event::MessageDelete(ref del) => del.channel_id,
+            Event::MessageDeleteBulk(ref del) => del.channel_id,
+            Event::ChannelPinsAck(ref ack) => ack.channel_id,
+            Event::ChannelPinsUpdate(ref update) => update.channel_id,
+            Event::ReactionAdd(ref reaction) => reaction.channel_id,
+            Event::ReactionRemove(ref reaction) => reaction.channel_id,
+            Event::ServerCreate(PossibleServer::Online(ref server)) => return Some(server.id),
+            Event::ServerCreate(PossibleServer::Offline(server_id)) => return Some(server_id),
+            Event::ServerUpdate(ref server) => return Some(server.id),
+            Event::ServerMemberAdd(server_id, _) => return Some(server_id),
+            Event::ServerMemberUpdate(ref update) => return Some(update.server_id),
+            Event::ServerMemberRemove(server_id, _) => return Some(server_id),
+            Event::ServerRoleCreate(server_id, _) => return Some(server_id),
+            Event::ServerRoleUpdate(server_id, _) => return Some(server_id),
+            Event::ServerRoleDelete(server_id, _) => return Some(server_id),
+            Event::ServerBanAdd(server_id, _) => return Some(server_id),
+            Event::ServerBanRemove(server_id, _) => return Some(server_id),
+            Event::ServerEmojisUpdate(server_id, _) => return Some(server_id),
+            Event::ChannelCreate(Channel::Public(ref channel)) => return Some(channel.server_id),
+            Event::ChannelUpdate(Channel::Public(ref channel)) => return Some(channel.server_id),
+            Event::ChannelDelete(Channel::Public(ref channel)) => return Some(channel.server_id),
+            _ => return None,
+        };
+        self.channels.get(&channel_id).cloned()
+    }
+}
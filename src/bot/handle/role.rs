@@ -27,7 +27,7 @@ impl Bot {
         // TODO: calculate diff
         // TODO: update role in cache
         let server = self.server_by_server(server_id)?;
-        self.log(server.log_channel, &format!("Role Changed: {:?}", role))?;
+        self.log_event(server.log_channel, &format!("Role Changed: {:?}", role))?;
         Ok(())
     }
 
@@ -38,7 +38,7 @@ impl Bot {
             unwrap!(server.roles.remove(&role_id));
         }
         let server = self.server_by_server(server_id)?;
-        self.log(server.log_channel, &format!("Role Deleted: {:?}", role_id))?;
+        self.log_event(server.log_channel, &format!("Role Deleted: {:?}", role_id))?;
         Ok(())
     }
 }
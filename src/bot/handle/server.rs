@@ -0,0 +1,24 @@
+use bot::Bot;
+
+use discord::model::Server;
+
+use errors::*;
+
+impl Bot {
+    /// Updates the cached server. Roles and emojis are changed by their own events.
+    pub fn handle_server_update(&mut self, update: Server) -> Result<()> {
+        let server_id = update.id;
+        {
+            let server = self.server_by_server_mut(server_id)?;
+            server.name = update.name;
+            server.owner_id = update.owner_id;
+            server.region = update.region;
+            server.icon = update.icon;
+            server.afk_timeout = update.afk_timeout;
+            server.afk_channel_id = update.afk_channel_id;
+            server.verification_level = update.verification_level;
+        }
+        self.check_names(server_id);
+        Ok(())
+    }
+}
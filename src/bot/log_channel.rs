@@ -0,0 +1,161 @@
+use std::io::{self, Write};
+
+use toml::Value;
+use discord::model::{
+    ServerId,
+    ChannelId,
+    RoleId,
+    PermissionOverwrite,
+    PermissionOverwriteType,
+    Permissions,
+};
+use discord::model::permissions::{
+    READ_MESSAGES,
+    SEND_MESSAGES,
+    EMBED_LINKS,
+    ATTACH_FILES,
+    READ_HISTORY,
+};
+
+use bot::Bot;
+use bot::server::Server;
+use bot::permissions::permission_names;
+use config::{Section, set_value};
+use errors::*;
+
+impl Bot {
+    /// Creates the log channel of a server which doesn't have one if `create_log_channel` is set.
+    ///
+    /// `@everyone` can't read the new channel, only the bot and the moderator role.
+    /// If the permissions can't be set, the channel is deleted again.
+    /// Its id is written to the config once it is restricted, so it is found again even if it is renamed.
+    pub fn create_log_channel(&mut self, server: &mut Server) -> Result<Option<ChannelId>> {
+        let (name, moderator_role) = match server.config {
+            Some(ref config) if config.create_log_channel == Some(true) => {
+                let name = config.log_channel_name.clone().unwrap_or_else(|| "log".to_string());
+                (name, config.moderator_role_id.map(RoleId))
+            },
+            _ => return Ok(None),
+        };
+        let mut overwrites = vec![
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(server.everyone_role()),
+                allow: Permissions::empty(),
+                deny: READ_MESSAGES,
+            },
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Member(self.user.id),
+                allow: READ_MESSAGES | SEND_MESSAGES | EMBED_LINKS | ATTACH_FILES | READ_HISTORY,
+                deny: Permissions::empty(),
+            },
+        ];
+        match moderator_role {
+            Some(role) if server.roles.contains_key(&role) => overwrites.push(PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(role),
+                allow: READ_MESSAGES | READ_HISTORY,
+                deny: Permissions::empty(),
+            }),
+            Some(role) => println!("Moderator role {} doesn't exist on server {:?}", role, server.name),
+            None => println!("No moderator_role_id given, only the bot can read the new log channel"),
+        }
+
+        // discord-rs can't create a channel together with its overwrites, so it is readable
+        // by everyone until they are set and must not be left behind if that fails
+        let mut channel = self.rest.create_channel(server.id, &name)?;
+        for overwrite in overwrites.iter() {
+            if let Err(err) = self.rest.create_permission(channel.id, overwrite.clone()) {
+                if let Err(err) = self.rest.delete_channel(channel.id) {
+                    writeln!(io::stderr(), "error: could not delete the new log channel {:?} of server {:?} \
+                        after failing to restrict it, it is readable by everyone: {}", name, server.name, err)
+                        .expect("failed writing to stderr");
+                }
+                return Err(err.into());
+            }
+        }
+        channel.permission_overwrites = overwrites;
+        let channel_id = channel.id;
+        println!("Created log channel {:?} for server {:?}", name, server.name);
+        self.channels.insert(channel_id, server.id);
+        server.channels.insert(channel_id, channel);
+        if let Some(ref mut config) = server.config {
+            config.log_channel_id = Some(channel_id.0);
+        }
+
+        // the channel is already usable, so only report if it can't be saved
+        if let Err(err) = self.save_log_channel_id(server, channel_id) {
+            writeln!(io::stderr(), "error: could not save the id of the new log channel in the config: {}", err)
+                .expect("failed writing to stderr");
+        }
+        Ok(Some(channel_id))
+    }
+
+    fn save_log_channel_id(&mut self, server: &Server, channel_id: ChannelId) -> Result<()> {
+        let index = unwrap!(self.server_config_index(server.id, &server.name), err ConfigError,
+            "server not found in the config");
+        let path = unwrap!(self.config.path.clone(), err ConfigError, "config wasn't loaded from a file");
+        set_value(&path, Section::ArrayEntry("server", index), "log_channel_id", &Value::Integer(channel_id.0 as i64))?;
+        self.config.server[index].log_channel_id = Some(channel_id.0);
+        Ok(())
+    }
+
+    /// Permissions the bot needs in the log channel of the server but doesn't have.
+    ///
+    /// If the bot or the log channel aren't known, nothing is reported as missing.
+    pub fn missing_permissions(&self, server: &Server) -> Permissions {
+        let channel = match server.log_channel.and_then(|id| server.channels.get(&id)) {
+            Some(channel) => channel,
+            None => return Permissions::empty(),
+        };
+        let perms = match server.member_permissions(self.user.id, channel) {
+            Some(perms) => perms,
+            None => return Permissions::empty(),
+        };
+        let mut needed = READ_MESSAGES | SEND_MESSAGES | EMBED_LINKS;
+        if server.config.as_ref().and_then(|c| c.long_message.as_ref()).map(|mode| mode == "attach").unwrap_or(false) {
+            needed.insert(ATTACH_FILES);
+        }
+        needed - perms
+    }
+
+    /// Tells stdout and the owner of the server which permissions the bot is missing in its log channel.
+    pub fn report_missing_permissions(&self, server: &Server) {
+        let channel = server.log_channel.and_then(|id| server.channels.get(&id))
+            .map(|channel| channel.name.clone())
+            .unwrap_or_default();
+        let msg = format!("The bot is missing the permissions {} in the log channel #{} of server {:?}. \
+            Nothing is logged for this server until they are given.",
+            permission_names(server.missing_permissions), channel, server.name);
+        println!("{}", msg);
+        // sent by the log queue, so the event loop doesn't wait for discord
+        self.queue.push_direct(server.owner_id, msg);
+    }
+
+    /// Checks the permissions of the bot in the log channel after roles, members or channels changed.
+    ///
+    /// Logging for the server is stopped while permissions are missing and resumed once they are given.
+    pub fn check_permissions(&mut self, server_id: ServerId) {
+        let (missing, was_missing) = match self.servers.get(&server_id) {
+            Some(server) => (self.missing_permissions(server), server.missing_permissions),
+            None => return,
+        };
+        if missing == was_missing {
+            return;
+        }
+        let log_channel = {
+            let server = self.servers.get_mut(&server_id).unwrap();
+            server.missing_permissions = missing;
+            server.log_channel
+        };
+        if log_channel.is_none() {
+            return;
+        }
+        let server = &self.servers[&server_id];
+        if !missing.is_empty() {
+            self.report_missing_permissions(server);
+        } else {
+            println!("The bot has all permissions it needs in the log channel of server {:?} again", server.name);
+            // ignore errors, this is only informational
+            let _ = self.log(log_channel, "Bot has all permissions it needs again and is logging to this channel.");
+        }
+    }
+}
@@ -1,138 +1,310 @@
 mod server;
 mod handle;
+mod filter;
+mod log_channel;
+mod names;
+mod permissions;
+mod queue;
+mod receive;
+mod split;
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender, Receiver};
+use std::time::Duration;
+use std::path::Path;
 
 use strfmt::strfmt;
-use discord::{Discord, Connection};
 use discord::model::{
     CurrentUser,
-    OnlineStatus,
     ServerId,
     ChannelId,
     LiveServer,
     PossibleServer,
+    ReadyEvent,
+    Event,
+    PublicChannel,
 };
 
 use errors::*;
-use config::Config;
+use config::{Config, ServerConfig, ConfigWatcher};
+use gateway::{RestClient, DiscordRest, RecordingRest};
 use self::server::Server;
+use self::queue::{LogQueue, Outgoing, MAX_MESSAGE_LEN};
+use self::receive::{Wakeup, ReceiveThread};
 
 pub struct Bot {
     config: Config,
-    dis: Discord,
-    con: Connection,
+    rest: Arc<RestClient>,
+    // events of the current gateway session and config reloads
+    wakeups: Receiver<Wakeup>,
+    wakeup_sender: Sender<Wakeup>,
+    // stops receiving from the current session once the bot is dropped
+    receiving: ReceiveThread,
+    queue: LogQueue,
     user: CurrentUser,
     servers: HashMap<ServerId, Server>,
     channels: HashMap<ChannelId, ServerId>,
+    watcher: Option<(ConfigWatcher, u64)>,
+    // set while an event which must not be logged is handled
+    ignore_event: bool,
+    // names which differ from the config and were already reported, by server and config key
+    reported_renames: HashMap<(ServerId, String), String>,
 }
 
 impl Bot {
-    pub fn new(config: Config) -> Result<Bot> {
-        let discord = Discord::from_bot_token(&unwrap!(config.bot.as_ref(), err ConfigError, "No bot token").token)?;
-        let (con, mut ready) = discord.connect()?;
-        println!("Logged in as {:?}", ready.user.username);
-        println!();
+    /// Connects to discord. If `record` is given, all received events are appended to that file.
+    pub fn new(config: Config, record: Option<&Path>) -> Result<Bot> {
+        let token = unwrap!(config.bot.as_ref().and_then(|b| b.token.clone()), err ConfigError, "No bot token");
+        let rest: Arc<RestClient> = Arc::new(DiscordRest::from_bot_token(&token)?);
+        let rest: Arc<RestClient> = match record {
+            Some(path) => Arc::new(RecordingRest::new(rest, &token, path)?),
+            None => rest,
+        };
+        Bot::with_client(config, rest)
+    }
+
+    /// Creates a bot which talks to discord through the given client.
+    pub fn with_client(config: Config, rest: Arc<RestClient>) -> Result<Bot> {
+        let (con, ready) = rest.connect()?;
+        let interval = config.bot.as_ref().and_then(|b| b.log_flush_interval_ms).unwrap_or(1000);
+        let (wakeup_sender, wakeups) = mpsc::channel();
+        let receiving = receive::spawn(con, wakeup_sender.clone());
         let mut bot = Bot {
             config: config,
-            dis: discord,
-            con: con,
-            user: ready.user,
+            queue: LogQueue::new(rest.clone(), Duration::from_millis(interval)),
+            rest: rest,
+            wakeups: wakeups,
+            wakeup_sender: wakeup_sender,
+            receiving: receiving,
+            user: ready.user.clone(),
             servers: HashMap::new(),
             channels: HashMap::new(),
+            watcher: None,
+            ignore_event: false,
+            reported_renames: HashMap::new(),
         };
-        bot.con.set_presence(None, OnlineStatus::Online, false);
+        bot.handle_ready(ready)?;
+        Ok(bot)
+    }
+
+    /// Initializes the state from a new gateway session.
+    ///
+    /// This is also called after a reconnect which couldn't resume the previous session.
+    /// In that case the servers are re-added, but their configuration and message cache is kept.
+    fn handle_ready(&mut self, mut ready: ReadyEvent) -> Result<()> {
+        println!("Logged in as {:?}", ready.user.username);
+        println!();
+        self.user = ready.user;
         for server in ready.servers.drain(..) {
             match server {
-                PossibleServer::Online(server) => bot.add_server(server)?,
+                PossibleServer::Online(server) => {
+                    let server_id = server.id;
+                    if let Err(err) = self.add_server(server) {
+                        self.handle_err("Ready", Some(server_id), err);
+                    }
+                },
                 _ => {}
             }
         }
-        Ok(bot)
+        Ok(())
     }
 
     fn add_server(&mut self, server: LiveServer) -> Result<()> {
+        // If we already know the server, this is a reconnect with a new session.
+        // In that case we keep its configuration and message cache.
+        let old = self.servers.remove(&server.id);
+        let (old_config, old_messages) = match old {
+            Some(old) => {
+                for channel_id in old.channels.keys() {
+                    self.channels.remove(channel_id);
+                }
+                (old.config, Some(old.messages))
+            },
+            None => (None, None)
+        };
+        let reconnected = old_messages.is_some();
         // regardless of if the server is configured or not, we need to have its full state
         // so we can keep it updated in case it is dynamically configured later
         for channel in server.channels.iter() {
             self.channels.insert(channel.id, server.id);
         }
-        // check if server is configured
-        let index = self.config.server.iter().position(|s| {
-            match s.server_id {
-                Some(id) => match s.server_name {
-                    Some(ref name) => server.id == ServerId(id) && server.name == *name,
-                    None => server.id == ServerId(id)
-                },
-                None => match s.server_name {
-                    Some(ref name) => server.name == *name,
-                    // This case has already been checked with config.validate() in main()
-                    None => unreachable!()
+        let server_config = match old_config.or_else(|| self.server_config(server.id, &server.name)) {
+            Some(config) => config,
+            // not in server config
+            None => {
+                let mut server = Server::new(server, None, None);
+                if let Some(messages) = old_messages {
+                    server.messages = messages;
                 }
+                let server_id = server.id;
+                self.servers.insert(server.id, server);
+                return Err(ErrorKind::ServerConfigError(server_id, "No configuration found".to_string()).into());
             }
-        });
-        // not in server config
-        if let None = index {
-            let server = Server::new(server, None, None);
-            let server_id = server.id;
-            self.servers.insert(server.id, server);
-            return Err(ErrorKind::ServerConfigError(server_id, "No configuration found".to_string()).into());
-        }
-        let server_config = self.config.server.swap_remove(index.unwrap());
-        let mut log_channel = None;
-        let mut channel_missing = false;
+        };
 
-        for channel in server.channels.iter() {
-            // check if this channel is the log channel
-            let is_log_channel = match server_config.log_channel_id {
-                Some(id) => match server_config.log_channel_name {
-                    Some(ref name) => channel.id == ChannelId(id) && channel.name == *name,
-                    None => channel.id == ChannelId(id)
-                },
-                None => match server_config.log_channel_name {
-                    Some(ref name) => channel.name == *name,
-                    None => {
-                        channel_missing = true;
-                        break;
-                    }
-                }
-            };
-            if is_log_channel {
-                log_channel = Some(channel.id);
-                break;
-            }
-        }
-        if channel_missing {
+        if server_config.log_channel_id.is_none() && server_config.log_channel_name.is_none()
+                && server_config.create_log_channel != Some(true) {
             println!("No log_channel_id or log_channel_name given to identify the channel.");
-            let server = Server::new(server, Some(server_config), None);
+            let mut server = Server::new(server, Some(server_config), None);
+            if let Some(messages) = old_messages {
+                server.messages = messages;
+            }
             self.servers.insert(server.id, server);
             return Ok(());
         }
-        let server = Server::new(server, Some(server_config), log_channel);
+        let log_channel = find_log_channel(&server_config, server.channels.iter());
+        let mut server = Server::new(server, Some(server_config), log_channel);
+        if let Some(messages) = old_messages {
+            server.messages = messages;
+        }
+        if log_channel.is_none() {
+            server.log_channel = self.try_create_log_channel(&mut server);
+        }
+        let log_channel = server.log_channel;
+        server.missing_permissions = self.missing_permissions(&server);
         if let None = log_channel {
             println!("Added Server but couldn't find log channel {:?}", server.name);
+        } else if !server.missing_permissions.is_empty() {
+            self.report_missing_permissions(&server);
+        } else if reconnected {
+            println!("Resumed logging for server {:?}", server.name);
         } else {
             println!("Successfully logging for server {:?}", server.name);
             self.log(server.log_channel, "Bot started successfully and is logging to this channel.")?;
         }
         println!();
+        let server_id = server.id;
         self.servers.insert(server.id, server);
+        self.check_names(server_id);
         Ok(())
     }
 
-    // returns if event was handled
-    // Otherwise the server needs to be restarted
-    fn handle_err(&mut self, err: Error) -> bool {
+    /// Creates the log channel of the server if configured, reporting errors to stderr.
+    fn try_create_log_channel(&mut self, server: &mut Server) -> Option<ChannelId> {
+        match self.create_log_channel(server) {
+            Ok(log_channel) => log_channel,
+            Err(err) => {
+                use ::std::io::Write;
+                writeln!(::std::io::stderr(), "error: could not create log channel for server {:?}: {}", server.name, err)
+                    .expect("failed writing to stderr");
+                None
+            }
+        }
+    }
+
+    /// Returns the configuration of the given server, if there is one.
+    fn server_config(&self, server_id: ServerId, server_name: &str) -> Option<ServerConfig> {
+        self.server_config_index(server_id, server_name).map(|index| self.config.server[index].clone())
+    }
+
+    /// Returns the index of the `[[server]]` entry of the given server.
+    ///
+    /// If an entry has a `server_id`, only the id is compared, so renaming the server doesn't
+    /// break the bot. Entries without an id are matched by their name.
+    fn server_config_index(&self, server_id: ServerId, server_name: &str) -> Option<usize> {
+        let servers = &self.config.server;
+        servers.iter().position(|s| s.server_id == Some(server_id.0)).or_else(|| {
+            servers.iter().position(|s| s.server_id.is_none() && s.server_name.as_ref().map(|name| name == server_name).unwrap_or(false))
+        })
+    }
+
+    /// Reloads the config whenever the config file was modified.
+    ///
+    /// A reloaded config is applied right away, without waiting for the next event.
+    pub fn watch_config(&mut self, watcher: ConfigWatcher, generation: u64) {
+        let sender = self.wakeup_sender.clone();
+        // stop being notified once this bot is gone
+        watcher.on_reload(move || sender.send(Wakeup::ConfigReloaded).is_ok());
+        self.watcher = Some((watcher, generation));
+        // the config might have been reloaded before we were notified
+        self.check_config_reload();
+    }
+
+    fn check_config_reload(&mut self) {
+        let config = match self.watcher {
+            Some((ref watcher, ref mut generation)) => watcher.changed(generation),
+            None => None,
+        };
+        if let Some(config) = config {
+            self.reload_config(config);
+        }
+    }
+
+    /// Replaces the config of every server with the one from the given config.
+    ///
+    /// Servers are attached to their log channel again, so servers which weren't
+    /// configured before start logging. Servers and log channels which were renamed on discord
+    /// stay attached as long as the reloaded config still names them like before.
+    /// Changes to the `bot` and `restart` sections only take effect once the bot is restarted.
+    fn reload_config(&mut self, config: Config) {
+        self.config = config;
+        let server_ids: Vec<_> = self.servers.keys().cloned().collect();
+        let mut announce = Vec::new();
+        for server_id in server_ids {
+            let (config, log_channel, was_logging) = {
+                let server = &self.servers[&server_id];
+                // a renamed server might still have its old name in the config
+                let configured_name = server.config.as_ref().and_then(|c| c.server_name.as_ref());
+                let config = self.server_config(server.id, &server.name)
+                    .or_else(|| configured_name.and_then(|name| self.server_config(server.id, name)));
+                let log_channel = config.as_ref().and_then(|c| reloaded_log_channel(server, c));
+                (config, log_channel, server.log_channel)
+            };
+            let create = log_channel.is_none() && config.is_some();
+            {
+                let server = self.servers.get_mut(&server_id).unwrap();
+                if config.is_none() && server.config.is_some() {
+                    println!("Stopped logging for server {:?}, it is not configured anymore", server.name);
+                }
+                server.config = config;
+                server.log_channel = log_channel;
+            }
+            if create {
+                // the server can't stay in the map while we create its channel
+                let mut server = self.servers.remove(&server_id).unwrap();
+                server.log_channel = self.try_create_log_channel(&mut server);
+                self.servers.insert(server_id, server);
+            }
+            self.check_permissions(server_id);
+            let server = &self.servers[&server_id];
+            if server.config.is_some() && server.log_channel.is_none() {
+                println!("Reloaded config of server {:?} but couldn't find its log channel", server.name);
+            } else if server.log_channel.is_some() && server.log_channel != was_logging
+                    && server.missing_permissions.is_empty() {
+                println!("Successfully logging for server {:?}", server.name);
+                announce.push(server.log_channel);
+            }
+        }
+        for log_channel in announce {
+            // ignore errors, this is only informational
+            let _ = self.log(log_channel, "Bot is now logging to this channel.");
+        }
+        let server_ids: Vec<_> = self.servers.keys().cloned().collect();
+        for server_id in server_ids {
+            self.check_names(server_id);
+        }
+    }
+
+    /// Reports an error which occurred while handling a single event.
+    ///
+    /// The error only affects that event, so the bot keeps processing the following ones.
+    /// Errors are always written to stderr. If the server the event belongs to is verbose,
+    /// they are logged to its log channel as well.
+    fn handle_err(&self, evt: &str, server_id: Option<ServerId>, err: Error) {
+        let server = server_id.and_then(|id| self.servers.get(&id));
         match err.kind() {
             &ErrorKind::ServerConfigError(server, ref msg) => {
-                let channel = self.server_by_server(server).unwrap().log_channel;
-                let _ = self.log(channel, msg);
-                return true;
+                if let Some(server) = self.servers.get(&server) {
+                    let _ = self.log(server.log_channel, msg);
+                }
+                return;
             },
             &ErrorKind::FormatError(server, ref err) => {
-                let channel = self.server_by_server(server).unwrap().log_channel;
-                let _ = self.log(channel, &err.to_string());
-                return true;
+                if let Some(server) = self.servers.get(&server) {
+                    let _ = self.log(server.log_channel, &err.to_string());
+                }
+                return;
             }
             _ => ()
         }
@@ -142,6 +314,7 @@ impl Bot {
         use ::std::io::Write as IoWrite;
         // first log for us
         let stderr = &mut ::std::io::stderr();
+        writeln!(stderr, "error while handling event {}", evt).expect("failed writing to stderr");
         writeln!(stderr, "error: {:?}", err).expect("failed writing to stderr");
         for e in err.iter().skip(1) {
             writeln!(stderr, "caused by: {:?}", e).expect("failed writing to stderr");
@@ -150,55 +323,112 @@ impl Bot {
             writeln!(stderr, "backtrace: {:?}", backtrace).expect("failed writing to stderr");
         }
 
-        // then log for all servers
+        // then log for the server the event belongs to
+        let server = match server {
+            Some(server) => server,
+            None => return,
+        };
+        let verbose = server.config.as_ref().and_then(|c| c.verbose).unwrap_or(false);
+        if server.log_channel.is_none() || !verbose {
+            return;
+        }
         writeln!(&mut errmsg, "error: {}", err).unwrap();
         for e in err.iter().skip(1) {
             writeln!(&mut errmsg, "caused by: {}", e).unwrap();
         }
+        // events like ServerCreate contain the whole server, which nobody wants to read
+        let evt: String = evt.chars().take(500).collect();
+        // ignore errors, there is nothing left we could report them to
+        let _ = self.log(server.log_channel, &format!(
+            "Failed to handle an event, skipping it. \
+            Please report this on https://discord.gg/5y7NKvj or github ( \
+            https://github.com/oberien/dilobo/issues/new ):\nevent: `{}`\n{}", evt, errmsg));
+    }
+
+    /// Tells all log channels that the bot was restarted after it stopped with the given error.
+    pub fn report_restart(&self, attempt: u32, reason: &str) {
         for server in self.servers.values() {
-            if server.log_channel.is_none() || server.config.is_none() {
+            if server.log_channel.is_none() {
                 continue;
             }
-            let log_channel = server.log_channel.unwrap();
-            let config = server.config.as_ref().unwrap();
-            if config.verbose.unwrap_or(false) {
-                // ignore errors, we are restarting anyways
-                let _ = self.log(Some(log_channel), &format!(
-                    "Got uncaught error and need to restart. \
-                    Please report this on https://discord.gg/5y7NKvj or github ( \
-                    https://github.com/oberien/dilobo/issues/new ):\n{}", errmsg));
-            } else {
-                // ignore errors, we are restarting anyways
-                let _ = self.log(Some(log_channel), "Got error and need to restart.");
-            }
+            // ignore errors, this is only informational
+            let _ = self.log(server.log_channel, &format!(
+                "Bot was restarted after an error (attempt {}): {}", attempt, reason));
         }
-        return false;
     }
 
-    pub fn run(&mut self) {
+    /// Receives and handles events until the connection is lost for good.
+    ///
+    /// discord-rs resumes the gateway session by itself if the connection drops.
+    /// If the session can't be resumed, it connects with a new session and emits `Ready`,
+    /// in which case the state is rebuilt from that session.
+    /// If even that fails, we try to connect once more before giving up.
+    /// Returns Ok once a scripted session has no events left. A live connection is never
+    /// left regularly, even if discord closes it with code 1000 it is resumed or reconnected.
+    pub fn run(&mut self) -> Result<()> {
         loop {
-            let evt = match self.con.recv_event() {
-                Ok(evt) => evt,
-                Err(err) => {
-                    if !self.handle_err(err.into()) {
-                        return;
-                    }
+            let res = match self.wakeups.recv().expect("the bot holds a sender itself") {
+                Wakeup::Event(res) => res,
+                Wakeup::ConfigReloaded => {
+                    self.check_config_reload();
                     continue;
-                }
+                },
             };
-            println!("evt: {:?}", evt);
-            match self.handle_event(evt) {
-                Ok(_) => (),
+            let evt = match res {
+                Ok(Some(evt)) => evt,
+                Ok(None) => return Ok(()),
+                // a single event which can't be decoded doesn't affect the connection
+                Err(::discord::Error::Decode(msg, value)) => {
+                    use ::std::io::Write;
+                    writeln!(::std::io::stderr(), "error: could not decode event: {}: {:?}", msg, value)
+                        .expect("failed writing to stderr");
+                    continue;
+                }
                 Err(err) => {
-                    if !self.handle_err(err) {
-                        return;
-                    }
+                    use ::std::io::Write;
+                    writeln!(::std::io::stderr(), "error: lost connection: {:?}", err)
+                        .expect("failed writing to stderr");
+                    self.reconnect()?;
+                    continue;
                 }
+            };
+            // a reload might be queued behind this event, apply it first so the event is handled with the new config
+            self.check_config_reload();
+            let description = format!("{:?}", evt);
+            println!("evt: {}", description);
+            let server_id = self.event_server(&evt);
+            let permissions_changed = match evt {
+                Event::ServerMemberUpdate(..) | Event::ServerRoleCreate(..) | Event::ServerRoleUpdate(..)
+                    | Event::ServerRoleDelete(..) | Event::ChannelUpdate(..) => true,
+                _ => false,
+            };
+            let res = match evt {
+                Event::Ready(ready) => self.handle_ready(ready),
+                Event::Resumed { .. } => {
+                    println!("Resumed gateway session");
+                    Ok(())
+                },
+                evt => self.handle_event(evt),
+            };
+            // errors of a single event must not stop the bot, only connection failures do
+            if let Err(err) = res {
+                self.handle_err(&description, server_id, err);
+            }
+            if let (true, Some(server_id)) = (permissions_changed, server_id) {
+                self.check_permissions(server_id);
             }
             println!();
         }
     }
 
+    fn reconnect(&mut self) -> Result<()> {
+        let (con, ready) = self.rest.connect()?;
+        // the previous receiving thread has exited after passing on the connection error,
+        // replacing it only closes what is left of its connection
+        self.receiving = receive::spawn(con, self.wakeup_sender.clone());
+        self.handle_ready(ready)
+    }
+
     fn server_by_channel(&self, channel_id: ChannelId) -> Result<&Server> {
         let server_id = unwrap!(self.channels.get(&channel_id),
                 "could not find server for channel {}", channel_id);
@@ -220,21 +450,122 @@ impl Bot {
             "could not find server for server_id {}", server_id))
     }
 
+    fn format(&self, log_channel: Option<ChannelId>, fmt: &String, map: &HashMap<String, String>) -> Result<String> {
+        match (strfmt(&fmt, map), log_channel) {
+            (Ok(msg), _) => Ok(msg),
+            (Err(err), Some(channel)) => Err(ErrorKind::FormatError(self.server_by_channel(channel)?.id, err).into()),
+            (Err(_), None) => Err(ErrorKind::ConfigError("No log channel found".to_string()).into())
+        }
+    }
+
     fn log_fmt(&self, log_channel: Option<ChannelId>, fmt: Option<&String>, map: &HashMap<String, String>) -> Result<()> {
+        if self.ignore_event {
+            return Ok(());
+        }
         if let Some(fmt) = fmt {
-            let msg = match (strfmt(&fmt, map), log_channel) {
-                (Ok(msg), _) => msg,
-                (Err(err), Some(channel)) => return Err(ErrorKind::FormatError(self.server_by_channel(channel)?.id, err).into()),
-                (Err(_), None) => return Err(ErrorKind::ConfigError("No log channel found".to_string()).into())
-            };
+            let msg = self.format(log_channel, fmt, map)?;
             self.log(log_channel, &msg)?;
         }
         Ok(())
     }
 
+    fn log_fmt_file(&self, log_channel: Option<ChannelId>, fmt: Option<&String>, map: &HashMap<String, String>,
+            filename: &str, content: String) -> Result<()> {
+        if self.ignore_event {
+            return Ok(());
+        }
+        if let Some(fmt) = fmt {
+            let msg = self.format(log_channel, fmt, map)?;
+            self.log_file(log_channel, &msg, filename, content)?;
+        }
+        Ok(())
+    }
+
+    /// Logs a message about the event which is handled, unless the event is filtered.
+    fn log_event(&self, log_channel: Option<ChannelId>, msg: &str) -> Result<()> {
+        if self.ignore_event {
+            return Ok(());
+        }
+        self.log(log_channel, msg)
+    }
+
+    /// Returns if the bot lacks permissions in the log channel.
+    ///
+    /// The server isn't known yet while it is added, in that case it wasn't checked yet either.
+    fn missing_log_permissions(&self, log_channel: ChannelId) -> bool {
+        self.channels.get(&log_channel)
+            .and_then(|server_id| self.servers.get(server_id))
+            .map(|server| !server.missing_permissions.is_empty())
+            .unwrap_or(false)
+    }
+
+    fn log_file(&self, log_channel: Option<ChannelId>, msg: &str, filename: &str, content: String) -> Result<()> {
+        let log_channel = unwrap!(log_channel, err ConfigError, "No log channel found");
+        if self.missing_log_permissions(log_channel) {
+            return Ok(());
+        }
+        self.queue.push(log_channel, Outgoing::File {
+            text: split::split(msg, MAX_MESSAGE_LEN).swap_remove(0),
+            filename: filename.to_string(),
+            content: content,
+        });
+        Ok(())
+    }
+
     fn log(&self, log_channel: Option<ChannelId>, msg: &str) -> Result<()> {
         let log_channel = unwrap!(log_channel, err ConfigError, "No log channel found");
-        self.dis.send_message(log_channel, msg, "", false).map(|_| ())?;
+        // discord would reject the message, so don't send anything until the permissions are fixed
+        if self.missing_log_permissions(log_channel) {
+            return Ok(());
+        }
+        if msg.len() <= MAX_MESSAGE_LEN {
+            self.queue.push(log_channel, Outgoing::Text(msg.to_string()));
+            return Ok(());
+        }
+        let attach = self.server_by_channel(log_channel)?.config.as_ref()
+            .and_then(|c| c.long_message.as_ref())
+            .map(|mode| mode == "attach")
+            .unwrap_or(false);
+        if attach {
+            let preview = split::split(msg, MAX_MESSAGE_LEN / 2).swap_remove(0);
+            let text = format!("{}\n*(message too long, full text attached)*", preview);
+            self.log_file(Some(log_channel), &text, "message.txt", msg.to_string())?;
+        } else {
+            for part in split::split(msg, MAX_MESSAGE_LEN) {
+                self.queue.push(log_channel, Outgoing::Text(part));
+            }
+        }
         Ok(())
     }
 }
+
+/// Returns the log channel of a server for its reloaded config.
+///
+/// The log channel in use is kept if the reloaded config still identifies it like the previous one,
+/// even if it was renamed on discord since. Otherwise it is looked up again.
+fn reloaded_log_channel(server: &Server, config: &ServerConfig) -> Option<ChannelId> {
+    if let (Some(id), Some(old)) = (server.log_channel, server.config.as_ref()) {
+        let unchanged = match (config.log_channel_id, old.log_channel_id) {
+            (Some(new), _) => new == id.0,
+            (None, Some(_)) => false,
+            (None, None) => config.log_channel_name.is_some() && config.log_channel_name == old.log_channel_name,
+        };
+        if unchanged && server.channels.contains_key(&id) {
+            return Some(id);
+        }
+    }
+    find_log_channel(config, server.channels.values())
+}
+
+/// Returns the log channel of a server among its channels.
+///
+/// If `log_channel_id` is given, the name isn't compared.
+fn find_log_channel<'a, I: Iterator<Item=&'a PublicChannel>>(config: &ServerConfig, mut channels: I) -> Option<ChannelId> {
+    channels.find(|channel| match config.log_channel_id {
+        Some(id) => channel.id == ChannelId(id),
+        None => match config.log_channel_name {
+            Some(ref name) => channel.name == *name,
+            None => false,
+        }
+    }).map(|channel| channel.id)
+}
@@ -0,0 +1,136 @@
+use std::io::{self, Write};
+
+use toml::Value;
+use discord::model::{ServerId, ChannelId};
+
+use bot::{Bot, find_log_channel};
+use config::{Section, set_value};
+use errors::*;
+
+impl Bot {
+    /// Warns if the server or its log channel are named differently than in its config.
+    ///
+    /// If `update_names` is set, the config file is updated with the new names.
+    /// Otherwise every new name is only reported once.
+    pub fn check_names(&mut self, server_id: ServerId) {
+        let (server_name, log_channel_name) = {
+            let server = match self.servers.get(&server_id) {
+                Some(server) => server,
+                None => return,
+            };
+            let config = match server.config {
+                Some(ref config) => config,
+                None => return,
+            };
+            let server_name = match config.server_name {
+                Some(ref name) if *name != server.name => Some((name.clone(), server.name.clone())),
+                _ => None,
+            };
+            let log_channel = server.log_channel.and_then(|id| server.channels.get(&id));
+            let log_channel_name = match (log_channel, config.log_channel_name.as_ref()) {
+                (Some(channel), Some(name)) if channel.name != *name => Some((name.clone(), channel.name.clone())),
+                _ => None,
+            };
+            (server_name, log_channel_name)
+        };
+        match server_name {
+            Some((old, new)) => self.renamed(server_id, "server_name", old, new),
+            None => { self.reported_renames.remove(&(server_id, "server_name".to_string())); },
+        }
+        match log_channel_name {
+            Some((old, new)) => self.renamed(server_id, "log_channel_name", old, new),
+            None => { self.reported_renames.remove(&(server_id, "log_channel_name".to_string())); },
+        }
+    }
+
+    /// Keeps the log channel of a server up to date with a renamed channel.
+    ///
+    /// If the log channel was renamed, it stays the log channel. If the server has no log channel
+    /// yet and the channel now has the configured name, it becomes the log channel.
+    pub fn channel_renamed(&mut self, server_id: ServerId, channel_id: ChannelId) {
+        let attach = {
+            let server = match self.servers.get(&server_id) {
+                Some(server) => server,
+                None => return,
+            };
+            let config = match server.config {
+                Some(ref config) => config,
+                None => return,
+            };
+            if server.log_channel == Some(channel_id) {
+                false
+            } else {
+                server.log_channel.is_none()
+                    && find_log_channel(config, server.channels.get(&channel_id).into_iter()).is_some()
+            }
+        };
+        if attach {
+            {
+                let server = self.servers.get_mut(&server_id).unwrap();
+                server.log_channel = Some(channel_id);
+                println!("Successfully logging for server {:?}", server.name);
+            }
+            // ignore errors, this is only informational
+            let _ = self.log(Some(channel_id), "Bot is now logging to this channel.");
+        }
+        self.check_names(server_id);
+    }
+
+    /// Reports that `key` of the server's config doesn't match the name on discord anymore
+    /// and writes the new name to the config if wanted.
+    fn renamed(&mut self, server_id: ServerId, key: &str, old: String, new: String) {
+        let (index, log_channel, update) = {
+            let server = &self.servers[&server_id];
+            let update = server.config.as_ref().and_then(|c| c.update_names).unwrap_or(false);
+            // the cached name may already be the new one, but entries without an id
+            // can only be found by the name they still have in the config
+            let name = server.config.as_ref().and_then(|c| c.server_name.as_ref()).unwrap_or(&server.name);
+            (self.server_config_index(server.id, name), server.log_channel, update)
+        };
+        if !update {
+            let reported = (server_id, key.to_string());
+            if self.reported_renames.get(&reported) == Some(&new) {
+                return;
+            }
+            self.reported_renames.insert(reported, new.clone());
+        }
+        let what = if key == "server_name" { "Server" } else { "Log channel" };
+        let mut msg = format!("{} was renamed from {:?} to {:?}", what, old, new);
+        if update {
+            match self.update_name(index, key, &new) {
+                Ok(()) => msg.push_str(", updated the config"),
+                Err(err) => {
+                    writeln!(io::stderr(), "error: could not update {} in the config: {}", key, err)
+                        .expect("failed writing to stderr");
+                    msg.push_str(&format!(", `{}` in the config needs to be updated", key));
+                }
+            }
+            if let Some(config) = self.servers.get_mut(&server_id).unwrap().config.as_mut() {
+                if key == "server_name" {
+                    config.server_name = Some(new.clone());
+                } else {
+                    config.log_channel_name = Some(new.clone());
+                }
+            }
+        } else {
+            msg.push_str(&format!(", `{}` in the config needs to be updated", key));
+        }
+        println!("{}", msg);
+        // ignore errors, this is only informational
+        let _ = self.log(log_channel, &msg);
+    }
+
+    /// Sets `key` of the `[[server]]` entry with the given index in the config file.
+    fn update_name(&mut self, index: Option<usize>, key: &str, name: &str) -> Result<()> {
+        let index = unwrap!(index, err ConfigError, "server not found in the config");
+        let path = unwrap!(self.config.path.clone(), err ConfigError, "config wasn't loaded from a file");
+        set_value(&path, Section::ArrayEntry("server", index), key, &Value::String(name.to_string()))?;
+        let config = &mut self.config.server[index];
+        if key == "server_name" {
+            config.server_name = Some(name.to_string());
+        } else {
+            config.log_channel_name = Some(name.to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use discord::model::{
+    RoleId,
+    UserId,
+    PublicChannel,
+    PermissionOverwrite,
+    PermissionOverwriteType,
+    Permissions,
+};
+use discord::model::permissions::ADMINISTRATOR;
+
+use bot::server::Server;
+use modelext::{ChannelPermission, MyVec};
+
+impl Server {
+    /// The `@everyone` role, which has the same id as the server.
+    pub fn everyone_role(&self) -> RoleId {
+        RoleId(self.id.0)
+    }
+
+    /// Effective permissions in the channel of a member who only has the given role.
+    ///
+    /// Returns `None` if the role is unknown.
+    pub fn role_permissions(&self, role_id: RoleId, channel: &PublicChannel) -> Option<Permissions> {
+        let everyone = self.everyone_role();
+        let base = self.roles.get(&everyone).map(|r| r.permissions).unwrap_or(Permissions::empty());
+        if role_id == everyone {
+            return Some(apply_overwrites(base, channel, everyone, &[], None));
+        }
+        let role = match self.roles.get(&role_id) {
+            Some(role) => role,
+            None => return None,
+        };
+        Some(apply_overwrites(base | role.permissions, channel, everyone, &[role_id], None))
+    }
+
+    /// Effective permissions of a member in the channel.
+    ///
+    /// Returns `None` if the member isn't cached.
+    pub fn member_permissions(&self, user_id: UserId, channel: &PublicChannel) -> Option<Permissions> {
+        let member = match self.members.get(&user_id) {
+            Some(member) => member,
+            None => return None,
+        };
+        if user_id == self.owner_id {
+            return Some(Permissions::all());
+        }
+        let everyone = self.everyone_role();
+        let base = member.roles.iter()
+            .chain(Some(&everyone))
+            .filter_map(|id| self.roles.get(id))
+            .fold(Permissions::empty(), |perms, role| perms | role.permissions);
+        Some(apply_overwrites(base, channel, everyone, &member.roles, Some(user_id)))
+    }
+}
+
+/// Applies the overwrites of the channel to the server wide permissions the same way
+/// discord does: first `@everyone`, then all roles at once, then the member.
+///
+/// Permissions which implicitly depend on others, like `send_messages` on `read_messages`,
+/// are kept as they are, so every change is reported on its own.
+fn apply_overwrites(mut perms: Permissions, channel: &PublicChannel, everyone: RoleId,
+        roles: &[RoleId], user: Option<UserId>) -> Permissions {
+    if perms.contains(ADMINISTRATOR) {
+        return Permissions::all();
+    }
+    let find = |kind: PermissionOverwriteType| -> Option<&PermissionOverwrite> {
+        channel.permission_overwrites.iter().find(|o| o.kind == kind)
+    };
+    if let Some(o) = find(PermissionOverwriteType::Role(everyone)) {
+        perms.remove(o.deny);
+        perms.insert(o.allow);
+    }
+    let mut allow = Permissions::empty();
+    let mut deny = Permissions::empty();
+    for &role in roles.iter().filter(|&&role| role != everyone) {
+        if let Some(o) = find(PermissionOverwriteType::Role(role)) {
+            allow.insert(o.allow);
+            deny.insert(o.deny);
+        }
+    }
+    perms.remove(deny);
+    perms.insert(allow);
+    if let Some(o) = user.and_then(|user| find(PermissionOverwriteType::Member(user))) {
+        perms.remove(o.deny);
+        perms.insert(o.allow);
+    }
+    perms
+}
+
+/// Names of the permissions as used in templates, separated by commas.
+pub fn permission_names(perms: Permissions) -> String {
+    let perms: MyVec<ChannelPermission> = perms.into();
+    perms.iter().map(|perm| perm.name()).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,216 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Instant, Duration};
+use std::cmp;
+use std::io::{self, Write};
+
+use discord;
+use discord::model::{ChannelId, UserId};
+
+use gateway::RestClient;
+
+/// Maximum number of characters discord allows in a single message.
+pub const MAX_MESSAGE_LEN: usize = 2000;
+
+/// A single queued log message.
+pub enum Outgoing {
+    /// Text of at most `MAX_MESSAGE_LEN`, which may be coalesced with adjacent texts.
+    Text(String),
+    /// Message with an uploaded text file.
+    File {
+        text: String,
+        filename: String,
+        content: String,
+    },
+}
+
+/// Something for the worker to send.
+enum Queued {
+    /// Log message for a log channel.
+    Log(ChannelId, Outgoing),
+    /// Direct message to a user, which is sent right away instead of being coalesced.
+    Direct(UserId, String),
+}
+
+/// Buffered queue of outgoing log lines.
+///
+/// Lines are sent from a separate thread, so the event loop never blocks on discord.
+/// Every `interval` the buffered lines of each log channel are coalesced into as few
+/// messages as possible. If discord answers with a rate limit, the channel is backed off
+/// for the requested time, which is doubled for every consecutive rate limit.
+pub struct LogQueue {
+    sender: Option<Sender<Queued>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl LogQueue {
+    pub fn new(rest: Arc<RestClient>, interval: Duration) -> LogQueue {
+        let (sender, receiver) = mpsc::channel();
+        let worker = thread::spawn(move || Worker::new(rest, receiver, interval).run());
+        LogQueue {
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn push(&self, channel: ChannelId, msg: Outgoing) {
+        // the worker only exits after the sender has been dropped
+        let _ = self.sender.as_ref().unwrap().send(Queued::Log(channel, msg));
+    }
+
+    /// Queues a direct message to the user, the private channel is opened by the worker as well.
+    pub fn push_direct(&self, user: UserId, text: String) {
+        let _ = self.sender.as_ref().unwrap().send(Queued::Direct(user, text));
+    }
+}
+
+impl Drop for LogQueue {
+    fn drop(&mut self) {
+        // disconnect the worker so it sends what is left and exits
+        drop(self.sender.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+struct Pending {
+    lines: VecDeque<Outgoing>,
+    retry_at: Option<Instant>,
+    backoff: Option<Duration>,
+}
+
+struct Worker {
+    rest: Arc<RestClient>,
+    receiver: Receiver<Queued>,
+    interval: Duration,
+    channels: HashMap<ChannelId, Pending>,
+}
+
+impl Worker {
+    fn new(rest: Arc<RestClient>, receiver: Receiver<Queued>, interval: Duration) -> Worker {
+        Worker {
+            rest: rest,
+            receiver: receiver,
+            interval: interval,
+            channels: HashMap::new(),
+        }
+    }
+
+    fn run(mut self) {
+        let mut next_flush = Instant::now() + self.interval;
+        loop {
+            let now = Instant::now();
+            let timeout = if next_flush > now { next_flush - now } else { Duration::from_secs(0) };
+            match self.receiver.recv_timeout(timeout) {
+                Ok(Queued::Log(channel, msg)) => {
+                    self.channels.entry(channel).or_insert_with(|| Pending {
+                        lines: VecDeque::new(),
+                        retry_at: None,
+                        backoff: None,
+                    }).lines.push_back(msg);
+                },
+                Ok(Queued::Direct(user, text)) => self.send_direct(user, &text),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.flush();
+                    next_flush = Instant::now() + self.interval;
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    // send everything which is left before exiting
+                    loop {
+                        self.flush();
+                        // only rate limited channels can have lines left
+                        let retry_at = self.channels.values()
+                            .filter(|p| !p.lines.is_empty())
+                            .filter_map(|p| p.retry_at)
+                            .min();
+                        match retry_at {
+                            Some(at) => {
+                                let now = Instant::now();
+                                if at > now {
+                                    thread::sleep(at - now);
+                                }
+                            },
+                            None => return,
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_direct(&self, user: UserId, text: &str) {
+        let res = self.rest.create_private_channel(user)
+            .and_then(|dm| self.rest.send_message(dm, text));
+        if let Err(err) = res {
+            writeln!(io::stderr(), "error: failed to send direct message to {}: {:?}", user, err)
+                .expect("failed writing to stderr");
+        }
+    }
+
+    fn flush(&mut self) {
+        let now = Instant::now();
+        for (&channel, pending) in self.channels.iter_mut() {
+            while !pending.lines.is_empty() {
+                if pending.retry_at.map(|at| at > now).unwrap_or(false) {
+                    break;
+                }
+                let (res, count) = match pending.lines.front() {
+                    Some(&Outgoing::File { ref text, ref filename, ref content }) =>
+                        (self.rest.send_file(channel, text, content.as_bytes(), filename), 1),
+                    _ => {
+                        let (text, count) = coalesce(&pending.lines);
+                        (self.rest.send_message(channel, &text), count)
+                    }
+                };
+                match res {
+                    Ok(_) => {
+                        pending.lines.drain(..count);
+                        pending.retry_at = None;
+                        pending.backoff = None;
+                    },
+                    Err(discord::Error::RateLimited(millis)) => {
+                        let retry_after = Duration::from_millis(millis);
+                        let backoff = match pending.backoff {
+                            Some(backoff) => cmp::max(backoff * 2, retry_after),
+                            None => retry_after,
+                        };
+                        pending.backoff = Some(backoff);
+                        pending.retry_at = Some(Instant::now() + backoff);
+                        break;
+                    },
+                    Err(err) => {
+                        // other errors won't get better by retrying, so drop the message
+                        writeln!(io::stderr(), "error: failed to send log message to {}: {:?}", channel, err)
+                            .expect("failed writing to stderr");
+                        pending.lines.drain(..count);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Joins as many consecutive texts as fit into a single message.
+/// Returns the message and the number of texts it contains, which is at least one.
+fn coalesce(lines: &VecDeque<Outgoing>) -> (String, usize) {
+    let mut text = String::new();
+    let mut count = 0;
+    for line in lines.iter() {
+        let line = match *line {
+            Outgoing::Text(ref line) => line,
+            Outgoing::File { .. } => break,
+        };
+        if count > 0 && text.len() + 1 + line.len() > MAX_MESSAGE_LEN {
+            break;
+        }
+        if count > 0 {
+            text.push('\n');
+        }
+        text += line;
+        count += 1;
+    }
+    (text, count)
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+
+use discord;
+use discord::model::{Event, OnlineStatus};
+
+use gateway::Gateway;
+
+/// Something the event loop has to handle.
+pub enum Wakeup {
+    /// Result of receiving from the gateway.
+    Event(discord::Result<Option<Event>>),
+    /// The config file was reloaded.
+    ConfigReloaded,
+}
+
+/// Thread receiving the events of a gateway session, which is stopped when this is dropped.
+///
+/// If the connection can be closed from another thread, dropping this closes it and waits
+/// for the thread to exit. Otherwise the thread exits and closes the connection as soon as
+/// it receives the next event.
+pub struct ReceiveThread {
+    stop: Arc<AtomicBool>,
+    closer: Option<Box<Fn() + Send>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for ReceiveThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(ref close) = self.closer {
+            close();
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Receives events on a separate thread, so the event loop can be woken up by other things as well.
+///
+/// The presence is set right away and whenever a new session starts.
+/// The thread exits once the connection failed, the scripted session has no events left,
+/// or it is stopped. Events which can't be decoded don't stop it.
+pub fn spawn(mut con: Box<Gateway>, sender: Sender<Wakeup>) -> ReceiveThread {
+    let stop = Arc::new(AtomicBool::new(false));
+    let closer = con.closer();
+    let stopped = stop.clone();
+    let thread = thread::spawn(move || {
+        con.set_presence(OnlineStatus::Online);
+        loop {
+            let res = con.recv_event();
+            if stopped.load(Ordering::SeqCst) {
+                // the event loop doesn't want anything from this session anymore
+                return;
+            }
+            let stop = match res {
+                Ok(Some(Event::Ready(..))) => {
+                    con.set_presence(OnlineStatus::Online);
+                    false
+                },
+                Ok(Some(_)) | Err(discord::Error::Decode(..)) => false,
+                Ok(None) | Err(_) => true,
+            };
+            if sender.send(Wakeup::Event(res)).is_err() || stop {
+                return;
+            }
+        }
+    });
+    ReceiveThread {
+        stop: stop,
+        closer: closer,
+        thread: Some(thread),
+    }
+}
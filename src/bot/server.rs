@@ -15,6 +15,7 @@ use discord::model::{
     VerificationLevel,
     EmojiId,
     Emoji,
+    Permissions,
 };
 
 use expiring_map::ExpiringMap;
@@ -45,6 +46,8 @@ pub struct Server {
     pub messages: ExpiringMap<MessageId, Message>,
     pub config: Option<ServerConfig>,
     pub log_channel: Option<ChannelId>,
+    // Permissions the bot needs but doesn't have in the log channel, nothing is logged while there are any
+    pub missing_permissions: Permissions,
 }
 
 impl Server {
@@ -65,6 +68,7 @@ impl Server {
             messages: ExpiringMap::new(Duration::from_secs(300)),
             config: config,
             log_channel: log_channel,
+            missing_permissions: Permissions::empty(),
         }
     }
 }
@@ -0,0 +1,159 @@
+use std::cmp;
+
+const FENCE: &'static str = "```";
+
+/// Splits a message into parts of at most `limit` bytes.
+///
+/// Messages are split at line boundaries if possible. Lines which are too long on their own
+/// are split at whitespace, or at any character as last resort.
+/// If a code block spans multiple parts, it is closed at the end of a part and reopened
+/// (including its language) at the start of the next one.
+pub fn split(text: &str, limit: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    // fence with the language of the code block we are currently in
+    let mut code_block: Option<String> = None;
+
+    for line in text.split('\n') {
+        // there must always be enough space to close the code block this line might open
+        // and to reopen the one we are in
+        let closing = FENCE.len() + 1;
+        let opened = if code_block.is_none() && line.contains(FENCE) {
+            Some(opening_fence(line, limit))
+        } else {
+            None
+        };
+        let reopening = code_block.as_ref().or(opened.as_ref()).map(|fence| fence.len() + 1).unwrap_or(0);
+        let budget = cmp::max(limit.saturating_sub(closing + reopening), 1);
+        for piece in split_line(line, budget) {
+            let separator = if current.is_empty() { 0 } else { 1 };
+            if current.len() + separator + piece.len() + closing > limit {
+                if code_block.is_some() {
+                    current.push('\n');
+                    current += FENCE;
+                }
+                parts.push(current);
+                current = String::new();
+                if let Some(ref fence) = code_block {
+                    current += fence;
+                }
+            }
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current += piece;
+            // an odd number of fences toggles whether we are inside a code block,
+            // which also applies to the following pieces of a long line
+            if piece.matches(FENCE).count() % 2 == 1 {
+                code_block = match code_block {
+                    Some(_) => None,
+                    None => Some(opening_fence(piece, limit)),
+                };
+            }
+        }
+    }
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Returns the fence opening the code block in the line, with its language if there is one.
+///
+/// Everything after the language is content of the code block, not part of the fence.
+/// Languages which would take up a large part of a message are dropped.
+fn opening_fence(line: &str, limit: usize) -> String {
+    let start = line.rfind(FENCE).unwrap() + FENCE.len();
+    let language: String = line[start..].chars().take_while(|c| !c.is_whitespace()).collect();
+    if FENCE.len() + language.len() > limit / 4 {
+        return FENCE.to_string();
+    }
+    format!("{}{}", FENCE, language)
+}
+
+/// Splits a single line into pieces of at most `limit` bytes, preferably at whitespace.
+fn split_line(mut line: &str, limit: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    while line.len() > limit {
+        let mut end = limit;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        // a single character which doesn't fit is put into a piece of its own
+        if end == 0 {
+            end = line.char_indices().nth(1).map(|(pos, _)| pos).unwrap_or(line.len());
+        }
+        let end = match line[..end].rfind(char::is_whitespace) {
+            Some(pos) if pos > 0 => pos,
+            _ => end,
+        };
+        pieces.push(&line[..end]);
+        line = line[end..].trim_start();
+    }
+    pieces.push(line);
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::*;
+    use super::*;
+
+    fn check(text: &str, limit: usize) -> Result<Vec<String>> {
+        let parts = split(text, limit);
+        for part in &parts {
+            assert!(part.len() <= limit, "part of {} bytes: {:?}", part.len(), part);
+            assert_eq!(part.matches(FENCE).count() % 2, 0, "unbalanced fences: {:?}", part);
+        }
+        Ok(parts)
+    }
+
+    #[test]
+    fn short_message_is_kept() -> Result<()> {
+        assert_eq!(split("foo\nbar", 2000), vec!["foo\nbar".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn splits_at_lines() -> Result<()> {
+        // space to close a code block is always kept free
+        assert_eq!(check("aaaa\nbbbb\ncccc", 13)?, vec!["aaaa\nbbbb", "cccc"]);
+        Ok(())
+    }
+
+    #[test]
+    fn reopens_code_block_with_language() -> Result<()> {
+        let parts = check("```rust\nlet a = 1;\nlet b = 2;\nlet c = 3;\n```", 30)?;
+        assert!(parts.len() > 1);
+        for part in &parts[1..] {
+            assert!(part.starts_with("```rust\n"), "{:?}", part);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn long_line_opening_code_block() -> Result<()> {
+        let line = format!("```rust {}", "word ".repeat(1000));
+        let parts = check(&format!("{}\nmore\n```", line), 2000)?;
+        assert!(parts.len() > 1);
+        for part in &parts[1..] {
+            assert!(part.starts_with("```rust\n"), "{:?}", part);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn long_language_is_dropped() -> Result<()> {
+        let text = format!("```{}\n{}\n```", "x".repeat(100), "y ".repeat(100));
+        check(&text, 50)?;
+        Ok(())
+    }
+
+    #[test]
+    fn multibyte_characters_are_not_cut() -> Result<()> {
+        let text = "ä".repeat(100);
+        let parts = check(&text, 7)?;
+        assert_eq!(parts.concat(), text);
+        Ok(())
+    }
+}
@@ -0,0 +1,365 @@
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+use toml::{self, Value};
+
+use errors::*;
+
+/// A section of the config file whose keys can be edited.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Section<'a> {
+    /// A table like `[bot]`, which is appended to the file if it doesn't exist.
+    Table(&'a str),
+    /// The entry with the given index of an array of tables like `[[server]]`.
+    ArrayEntry(&'a str, usize),
+}
+
+/// Sets `key = value` in the given section of a TOML file.
+///
+/// Everything else in the file, including comments, formatting and sections we don't know,
+/// is kept as it is. If the key exists, its value is replaced, keeping a comment after it.
+/// Values spanning multiple lines, like arrays and `"""` strings, are replaced completely.
+/// Otherwise the key is added after the last key of the section.
+/// The file is written to a temporary file first, which then replaces the original.
+pub fn set_value<P: AsRef<Path>>(path: P, section: Section, key: &str, value: &Value) -> Result<()> {
+    let path = path.as_ref();
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    let edited = edit(&text, section, key, value)?;
+    // never write something we couldn't load again
+    toml::from_str::<Value>(&edited)
+        .chain_err(|| format!("editing {} would make it invalid", path.display()))?;
+    write_atomic(path, &edited)
+}
+
+fn edit(text: &str, section: Section, key: &str, value: &Value) -> Result<String> {
+    let mut lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+    let open = open_lines(&lines);
+    let (start, end) = match find_section(&lines, &open, section) {
+        Some(range) => range,
+        None => match section {
+            Section::Table(name) => {
+                if lines.last().map(|line| !line.trim().is_empty()).unwrap_or(false) {
+                    lines.push(String::new());
+                }
+                lines.push(format!("[{}]", name));
+                let len = lines.len();
+                (len, len)
+            },
+            Section::ArrayEntry(name, index) => return Err(ErrorKind::ConfigError(format!(
+                "could not find entry {} of [[{}]] in the config file", index, name)).into()),
+        }
+    };
+
+    let is_key = |i: usize, lines: &[String]| !open[i] && line_key(&lines[i]).is_some();
+    let existing = (start..end).find(|&i| !open[i] && line_key(&lines[i]) == Some(key));
+    match existing {
+        Some(i) => {
+            let (last, comment) = value_end(&lines, i);
+            let eq = lines[i].find('=').unwrap();
+            let mut new = format!("{}= {}", &lines[i][..eq], value);
+            if let Some(comment) = comment {
+                new = format!("{} {}", new, comment);
+            }
+            lines.drain(i + 1..last + 1);
+            lines[i] = new;
+        },
+        None => {
+            // add after the last key, so trailing comments and blank lines stay in front of the next section
+            let pos = (start..end).rev().find(|&i| is_key(i, &lines)).map(|i| value_end(&lines, i).0 + 1).unwrap_or(start);
+            lines.insert(pos, format!("{} = {}", key, value));
+        }
+    }
+
+    let mut res = lines.join("\n");
+    if text.ends_with('\n') || text.is_empty() {
+        res.push('\n');
+    }
+    Ok(res)
+}
+
+/// Returns the range of lines holding the keys of the section.
+///
+/// Keys of a section end at the next header, including subtables like `[server.member_filter]`.
+/// Lines within values spanning multiple lines are never headers.
+fn find_section(lines: &[String], open: &[bool], section: Section) -> Option<(usize, usize)> {
+    let mut entries = 0;
+    let mut start = None;
+    for (i, line) in lines.iter().enumerate() {
+        if open[i] {
+            continue;
+        }
+        let header = match header(line) {
+            Some(header) => header,
+            None => continue,
+        };
+        if start.is_some() {
+            return Some((start.unwrap(), i));
+        }
+        match (section, header) {
+            (Section::Table(name), (false, h)) if h == name => start = Some(i + 1),
+            (Section::ArrayEntry(name, index), (true, h)) if h == name => {
+                if entries == index {
+                    start = Some(i + 1);
+                }
+                entries += 1;
+            },
+            _ => {},
+        }
+    }
+    start.map(|start| (start, lines.len()))
+}
+
+/// Parses a table header, returning if it is an array of tables and its name.
+fn header(line: &str) -> Option<(bool, &str)> {
+    let line = line.trim();
+    let line = match comment_start(line) {
+        Some(pos) => line[..pos].trim(),
+        None => line,
+    };
+    if line.starts_with("[[") && line.ends_with("]]") {
+        Some((true, line[2..line.len() - 2].trim()))
+    } else if line.starts_with('[') && line.ends_with(']') {
+        Some((false, line[1..line.len() - 1].trim()))
+    } else {
+        None
+    }
+}
+
+/// Returns the key of a `key = value` line.
+fn line_key(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') || trimmed.starts_with('[') {
+        return None;
+    }
+    let eq = match trimmed.find('=') {
+        Some(eq) => eq,
+        None => return None,
+    };
+    Some(trimmed[..eq].trim().trim_matches('"'))
+}
+
+/// Returns for every line if it starts within a value which spans multiple lines.
+fn open_lines(lines: &[String]) -> Vec<bool> {
+    let mut res = Vec::with_capacity(lines.len());
+    let mut state = ValueState::default();
+    for line in lines {
+        res.push(state.is_open());
+        if state.is_open() {
+            state = scan_value(line, state).0;
+        } else if line_key(line).is_some() {
+            let eq = line.find('=').unwrap();
+            state = scan_value(&line[eq + 1..], state).0;
+        }
+    }
+    res
+}
+
+/// Returns the last line of the value of the key in line `i` and the comment after the value.
+fn value_end(lines: &[String], i: usize) -> (usize, Option<&str>) {
+    let eq = lines[i].find('=').unwrap();
+    let (mut state, comment) = scan_value(&lines[i][eq + 1..], ValueState::default());
+    let mut comment = comment.map(|pos| &lines[i][eq + 1 + pos..]);
+    let mut last = i;
+    while state.is_open() && last + 1 < lines.len() {
+        last += 1;
+        let (next, pos) = scan_value(&lines[last], state);
+        state = next;
+        comment = pos.map(|pos| &lines[last][pos..]);
+    }
+    (last, comment)
+}
+
+/// What is still open after scanning a part of a value.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+struct ValueState {
+    /// Number of open arrays.
+    depth: usize,
+    /// Delimiter of the open multi-line string.
+    string: Option<&'static str>,
+}
+
+impl ValueState {
+    fn is_open(&self) -> bool {
+        self.depth > 0 || self.string.is_some()
+    }
+}
+
+/// Scans (a line of) a value, returning what is open after it and where a comment starts.
+fn scan_value(text: &str, mut state: ValueState) -> (ValueState, Option<usize>) {
+    // all delimiters are ascii, so they can't be confused with parts of other characters
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(delim) = state.string {
+            if delim == "\"\"\"" && bytes[i] == b'\\' {
+                i += 2;
+            } else if bytes[i..].starts_with(delim.as_bytes()) {
+                state.string = None;
+                i += delim.len();
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+        match bytes[i] {
+            b'#' => return (state, Some(i)),
+            quote @ b'"' | quote @ b'\'' => {
+                let multi = if quote == b'"' { "\"\"\"" } else { "'''" };
+                if bytes[i..].starts_with(multi.as_bytes()) {
+                    state.string = Some(multi);
+                    i += multi.len();
+                    continue;
+                }
+                // other strings end on the same line
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if quote == b'"' && bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            },
+            b'[' => {
+                state.depth += 1;
+                i += 1;
+            },
+            b']' => {
+                state.depth = state.depth.saturating_sub(1);
+                i += 1;
+            },
+            _ => i += 1,
+        }
+    }
+    (state, None)
+}
+
+/// Returns where a comment starts in the line, ignoring `#` within strings.
+fn comment_start(line: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            },
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return Some(i),
+            _ => {},
+        }
+        escaped = false;
+    }
+    None
+}
+
+/// Writes the file through a temporary file in the same directory, so it is either
+/// completely replaced or not at all.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let tmp = temp_path(path);
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    if let Ok(meta) = fs::metadata(path) {
+        fs::set_permissions(&tmp, meta.permissions())?;
+    }
+    if let Err(err) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Value;
+
+    use errors::*;
+    use super::{edit, Section};
+
+    const CONFIG: &'static str = r#"[bot]
+admins = [
+    1, # alice
+    2,
+] # the admins
+token = "a # b" # not a comment in the string
+
+[[server]]
+server_id = 100
+greeting = """
+[[server]]
+server_id = 200
+"""
+quotes = '''
+[bot]
+'''
+filter = ["[", "]"]
+
+[server.member_filter]
+names = ["x"]
+
+[[server]]
+server_id = 300
+"#;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn replace_multi_line_array() -> Result<()> {
+        let edited = edit(CONFIG, Section::Table("bot"), "admins", &Value::Integer(3))?;
+        assert!(edited.starts_with("[bot]\nadmins = 3 # the admins\ntoken = "), "{}", edited);
+        Ok(())
+    }
+
+    #[test]
+    fn keep_inline_comment() -> Result<()> {
+        let edited = edit(CONFIG, Section::Table("bot"), "token", &string("c"))?;
+        assert!(edited.contains("\ntoken = \"c\" # not a comment in the string\n"), "{}", edited);
+        Ok(())
+    }
+
+    #[test]
+    fn replace_multi_line_string() -> Result<()> {
+        let edited = edit(CONFIG, Section::ArrayEntry("server", 0), "greeting", &string("hi"))?;
+        assert!(edited.contains("server_id = 100\ngreeting = \"hi\"\nquotes = '''\n"), "{}", edited);
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_headers_and_keys_in_strings() -> Result<()> {
+        // the `[[server]]` within the string is not the second entry
+        let edited = edit(CONFIG, Section::ArrayEntry("server", 1), "server_id", &Value::Integer(400))?;
+        assert!(edited.contains("\"\"\"\n[[server]]\nserver_id = 200\n\"\"\""), "{}", edited);
+        assert!(edited.ends_with("[[server]]\nserver_id = 400\n"), "{}", edited);
+        // neither is the `[bot]` within the literal string
+        let edited = edit(CONFIG, Section::Table("bot"), "log_flush_interval_ms", &Value::Integer(5))?;
+        assert!(edited.contains("token = \"a # b\" # not a comment in the string\nlog_flush_interval_ms = 5\n"), "{}", edited);
+        Ok(())
+    }
+
+    #[test]
+    fn add_to_entry_which_is_not_the_last() -> Result<()> {
+        let edited = edit(CONFIG, Section::ArrayEntry("server", 0), "log_channel", &Value::Integer(5))?;
+        assert!(edited.contains("filter = [\"[\", \"]\"]\nlog_channel = 5\n\n[server.member_filter]"), "{}", edited);
+        assert!(edited.ends_with("[[server]]\nserver_id = 300\n"), "{}", edited);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_entry() -> Result<()> {
+        assert!(edit(CONFIG, Section::ArrayEntry("server", 2), "server_id", &Value::Integer(1)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Read;
+
+use toml::{self, Value};
+use toml::de::Deserializer;
+use serde_ignored;
+
+use errors::*;
+
+mod watch;
+mod templates;
+mod edit;
+mod token;
+
+/// Built-in defaults of every server key.
+const DEFAULTS: &'static str = include_str!("defaults.toml");
+/// Commented config, the built-in defaults are inserted at `# @templates`.
+/// `Config.toml` in the root of the repository is generated from it.
+const SAMPLE: &'static str = include_str!("sample.toml");
+
+pub use self::watch::ConfigWatcher;
+pub use self::edit::{Section, set_value};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Config {
+    pub bot: Option<BotConfig>,
+    pub restart: Option<RestartConfig>,
+    // Only set while loading, afterwards it has been applied to every server
+    pub defaults: Option<ServerConfig>,
+    pub server: Vec<ServerConfig>,
+    // File the config was loaded from, changes made by the bot are written back to it
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BotConfig {
+    // The token can also be given by other means, see `Config::resolve_token`
+    pub token: Option<String>,
+    // File containing the token
+    pub token_file: Option<String>,
+    // Interval in which queued log messages are sent
+    pub log_flush_interval_ms: Option<u64>,
+    // Interval in which Config.toml is checked for changes, 0 disables reloading
+    pub config_poll_interval_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestartConfig {
+    // Delay before the first restart, doubled for every consecutive failure
+    pub initial_delay_ms: Option<u64>,
+    pub max_delay_ms: Option<u64>,
+    // Number of consecutive failures after which the bot exits
+    pub max_retries: Option<u32>,
+    // A run lasting at least this long resets the number of consecutive failures
+    pub reset_after_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServerConfig {
+    pub server_id: Option<u64>,
+    pub server_name: Option<String>,
+    pub log_channel_id: Option<u64>,
+    pub log_channel_name: Option<String>,
+    pub verbose: Option<bool>,
+    // Write the new name of the server or log channel back to the config if they are renamed
+    pub update_names: Option<bool>,
+    // Create the log channel if it doesn't exist, readable only by the bot and the moderator role
+    pub create_log_channel: Option<bool>,
+    pub moderator_role_id: Option<u64>,
+    // How to log messages longer than discord allows: "split" or "attach"
+    pub long_message: Option<String>,
+    // Filters applied to all events unless overridden by the event category
+    pub ignored_channels: Option<Vec<u64>>,
+    pub ignored_users: Option<Vec<u64>>,
+    pub ignored_roles: Option<Vec<u64>>,
+    pub ignore_bots: Option<bool>,
+    pub message_filter: Option<FilterConfig>,
+    pub member_filter: Option<FilterConfig>,
+    pub role_filter: Option<FilterConfig>,
+    pub ban_filter: Option<FilterConfig>,
+    pub emoji_filter: Option<FilterConfig>,
+    pub channel_filter: Option<FilterConfig>,
+    pub reaction_filter: Option<FilterConfig>,
+    // Format strings for events
+    //pub ready_msg: Option<String>,
+    //pub resumed_msg: Option<String>,
+    //pub user_update_msg: Option<String>,
+    //pub user_note_update_msg: Option<String>,
+    //pub user_settings_update_msg: Option<String>,
+    //pub user_server_settings_update_msg: Option<String>,
+    // TODO: Differenciate between updates
+    //pub voice_state_update_msg: Option<String>,
+    //pub call_create_msg: Option<String>,
+    // TODO: Differenciate between updates
+    //pub call_update_msg: Option<String>,
+    //pub call_delete_msg: Option<String>,
+    //pub channel_recipient_add_msg: Option<String>,
+    //pub channel_recipient_remove_msg: Option<String>,
+    //pub typing_start_msg: Option<String>,
+    // TODO: Differenciate between updates
+    //pub presence_update_msg: Option<String>,
+    //pub presences_replace_msg: Option<String>,
+    //pub relationship_add_msg: Option<String>,
+    //pub relationship_remove_msg: Option<String>,
+    pub message_create_msg: Option<String>,
+    pub message_update_uncached_msg: Option<String>,
+    pub message_update_kind_msg: Option<String>,
+    pub message_update_content_msg: Option<String>,
+    // If the old or new content is longer than this, message_update_content_diff_msg
+    // is used instead and a diff of the content is attached as file.
+    // Without message_update_content_diff_msg, message_update_content_msg is used for all edits.
+    pub message_update_content_diff_threshold: Option<usize>,
+    pub message_update_content_diff_msg: Option<String>,
+    pub message_update_nonce_msg: Option<String>,
+    pub message_update_tts_msg: Option<String>,
+    pub message_update_pinned_msg: Option<String>,
+    pub message_update_unpinned_msg: Option<String>,
+    pub message_update_edited_time_msg: Option<String>,
+    pub message_update_mention_everyone_msg: Option<String>,
+    pub message_update_mention_added_msg: Option<String>,
+    pub message_update_mention_removed_msg: Option<String>,
+    pub message_update_mention_role_added_msg: Option<String>,
+    pub message_update_mention_role_removed_msg: Option<String>,
+    pub message_update_attachment_added_msg: Option<String>,
+    pub message_update_attachment_removed_msg: Option<String>,
+    pub message_update_embeds_added_msg: Option<String>,
+    pub message_update_embeds_removed_msg: Option<String>,
+    pub message_delete_cached_msg: Option<String>,
+    pub message_delete_uncached_msg: Option<String>,
+    pub message_delete_bulk_msg: Option<String>,
+    // TODO: Differenciate between updates
+    //pub server_update_msg: Option<String>,
+    pub server_member_add_msg: Option<String>,
+    pub server_member_role_add_msg: Option<String>,
+    pub server_member_role_remove_msg: Option<String>,
+    pub server_member_nick_change_msg: Option<String>,
+    pub server_member_no_change_msg: Option<String>,
+    pub server_member_remove_msg: Option<String>,
+    //pub server_members_chunk_msg: Option<String>,
+    //pub server_sync_msg: Option<String>,
+    pub server_role_create_msg: Option<String>,
+    // TODO: Differenciate between updates
+    //pub server_role_update_msg: Option<String>,
+    //pub server_role_delete_msg: Option<String>,
+    pub server_ban_add_msg: Option<String>,
+    pub server_ban_remove_msg: Option<String>,
+    pub server_emoji_add_msg: Option<String>,
+    pub server_emoji_remove_msg: Option<String>,
+    pub server_emoji_name_change_msg: Option<String>,
+    pub channel_create_msg: Option<String>,
+    pub channel_update_no_change_msg: Option<String>,
+    pub channel_update_name_msg: Option<String>,
+    pub channel_update_user_overwrite_add_msg: Option<String>,
+    pub channel_update_user_overwrite_remove_msg: Option<String>,
+    pub channel_update_role_overwrite_add_msg: Option<String>,
+    pub channel_update_role_overwrite_remove_msg: Option<String>,
+    pub channel_update_user_perms_msg: Option<String>,
+    pub channel_update_role_perms_msg: Option<String>,
+    pub channel_update_topic_msg: Option<String>,
+    pub channel_update_position_msg: Option<String>,
+    pub channel_update_bitrate_msg: Option<String>,
+    pub channel_update_user_limit_msg: Option<String>,
+    /// Effective permissions a role or user gained or lost by changed overwrites.
+    /// The analysis is only done if at least one of these is given.
+    pub channel_update_role_gained_msg: Option<String>,
+    pub channel_update_role_lost_msg: Option<String>,
+    pub channel_update_user_gained_msg: Option<String>,
+    pub channel_update_user_lost_msg: Option<String>,
+    pub channel_delete_msg: Option<String>,
+    //pub channel_pins_ack_msg: Option<String>,
+    // TODO: Differenciate between updates
+    //pub channel_pins_update_msg: Option<String>,
+    pub reaction_add_cached_msg: Option<String>,
+    pub reaction_add_uncached_msg: Option<String>,
+    pub reaction_remove_cached_msg: Option<String>,
+    pub reaction_remove_uncached_msg: Option<String>,
+}
+
+/// Per-category override of the server's filters.
+/// Every field which is given replaces the corresponding server-wide filter.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilterConfig {
+    pub ignored_channels: Option<Vec<u64>>,
+    pub ignored_users: Option<Vec<u64>>,
+    pub ignored_roles: Option<Vec<u64>>,
+    pub ignore_bots: Option<bool>,
+}
+
+impl Config {
+    /// Reads the config file and fills in the defaults, without validating it.
+    ///
+    /// Errors include the position in the file. Unknown keys are errors as well,
+    /// so misspelled keys don't silently fall back to their default.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
+        let path = path.as_ref();
+        let mut text = String::new();
+        File::open(path).chain_err(|| format!("Failed to open config file {}", path.display()))?
+            .read_to_string(&mut text)?;
+
+        // decode the text itself first, as only its errors know where they happened
+        let mut unknown = Vec::new();
+        {
+            let mut deserializer = Deserializer::new(&text);
+            let res: ::std::result::Result<Config, _> = serde_ignored::deserialize(&mut deserializer,
+                |key| unknown.push(key.to_string()));
+            if let Err(err) = res.and_then(|_| deserializer.end()) {
+                return Err(decode_error(path, err));
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(ErrorKind::ConfigError(format!("Unknown keys in {}: {}",
+                path.display(), unknown.join(", "))).into());
+        }
+
+        let mut value: Value = toml::from_str(&text).map_err(|err| decode_error(path, err))?;
+        apply_defaults(&mut value)?;
+        let mut config: Config = value.try_into().map_err(|err| decode_error(path, err))?;
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Validates the config.
+    pub fn check(&self) -> Result<()> {
+        if let Some(ref restart) = self.restart {
+            if restart.initial_delay_ms == Some(0) {
+                return Err(ErrorKind::ConfigError("restart.initial_delay_ms must be greater than 0".to_string()).into())
+            }
+        }
+        for server in self.server.iter() {
+            if server.server_name.is_none() && server.server_id.is_none() {
+                return Err(ErrorKind::ConfigError("No server_id or server_name given".to_string()).into())
+            }
+            match server.long_message.as_ref().map(|s| s.as_str()) {
+                None | Some("split") | Some("attach") => {},
+                Some(mode) => return Err(ErrorKind::ConfigError(format!(
+                        "Invalid long_message {:?}, expected \"split\" or \"attach\"", mode)).into())
+            }
+        }
+        // report all broken templates at once instead of failing on the first one
+        let mut report = String::new();
+        for server in self.server.iter() {
+            let name = match (server.server_id, server.server_name.as_ref()) {
+                (Some(id), _) => id.to_string(),
+                (None, Some(name)) => format!("{:?}", name),
+                (None, None) => unreachable!(),
+            };
+            for problem in server.check_templates() {
+                report += &format!("\n  server {}: {}", name, problem);
+            }
+        }
+        if !report.is_empty() {
+            return Err(ErrorKind::ConfigError(format!("Invalid templates:{}", report)).into());
+        }
+        Ok(())
+    }
+}
+
+/// Returns a config file with every option explained, and the built-in defaults commented out.
+pub fn default_config() -> String {
+    let defaults: Vec<_> = DEFAULTS.lines()
+        // skip the header of the file
+        .skip_while(|line| !line.trim().is_empty())
+        .skip(1)
+        .map(|line| if line.is_empty() || line.starts_with('#') {
+            line.to_string()
+        } else {
+            format!("# {}", line)
+        })
+        .collect();
+    SAMPLE.replace("# @templates\n", &(defaults.join("\n") + "\n"))
+}
+
+fn decode_error(path: &Path, err: toml::de::Error) -> Error {
+    let msg = match err.line_col() {
+        Some((line, col)) => format!("{}:{}:{}: {}", path.display(), line + 1, col + 1, err),
+        None => format!("{}: {}", path.display(), err),
+    };
+    ErrorKind::ConfigError(msg).into()
+}
+
+/// Fills in every key a server doesn't set from the `[defaults]` section of the config,
+/// and every key not given there from the built-in defaults.
+///
+/// Templates set to an empty string are removed afterwards, which disables them.
+fn apply_defaults(config: &mut Value) -> Result<()> {
+    let config = match *config {
+        Value::Table(ref mut config) => config,
+        _ => return Ok(()),
+    };
+    let mut defaults = match toml::from_str(DEFAULTS).expect("built-in defaults are invalid") {
+        Value::Table(defaults) => defaults,
+        _ => unreachable!(),
+    };
+    match config.remove("defaults") {
+        Some(Value::Table(overrides)) => defaults.extend(overrides),
+        Some(_) => return Err(ErrorKind::ConfigError("defaults must be a table".to_string()).into()),
+        None => {}
+    }
+    let servers = match config.get_mut("server") {
+        Some(&mut Value::Array(ref mut servers)) => servers,
+        _ => return Ok(()),
+    };
+    for server in servers.iter_mut() {
+        let server = match *server {
+            Value::Table(ref mut server) => server,
+            _ => continue,
+        };
+        for (key, value) in defaults.iter() {
+            server.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        let disabled: Vec<_> = server.iter()
+            .filter(|&(key, value)| key.ends_with("_msg") && *value == Value::String(String::new()))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in disabled {
+            server.remove(&key);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::*;
+    use super::{Config, default_config};
+
+    #[test]
+    fn config_toml_is_generated() -> Result<()> {
+        assert!(include_str!("../../Config.toml") == default_config(),
+            "Config.toml is outdated, regenerate it with `dilobo print-default-config > Config.toml`");
+        Config::load("Config.toml")?.check()
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use strfmt::strfmt;
+
+use config::ServerConfig;
+
+impl ServerConfig {
+    /// Every template of the server by the name of its field.
+    pub fn templates(&self) -> Vec<(&'static str, Option<&String>)> {
+        vec![
+            ("message_create_msg", self.message_create_msg.as_ref()),
+            ("message_update_uncached_msg", self.message_update_uncached_msg.as_ref()),
+            ("message_update_kind_msg", self.message_update_kind_msg.as_ref()),
+            ("message_update_content_msg", self.message_update_content_msg.as_ref()),
+            ("message_update_content_diff_msg", self.message_update_content_diff_msg.as_ref()),
+            ("message_update_nonce_msg", self.message_update_nonce_msg.as_ref()),
+            ("message_update_tts_msg", self.message_update_tts_msg.as_ref()),
+            ("message_update_pinned_msg", self.message_update_pinned_msg.as_ref()),
+            ("message_update_unpinned_msg", self.message_update_unpinned_msg.as_ref()),
+            ("message_update_edited_time_msg", self.message_update_edited_time_msg.as_ref()),
+            ("message_update_mention_everyone_msg", self.message_update_mention_everyone_msg.as_ref()),
+            ("message_update_mention_added_msg", self.message_update_mention_added_msg.as_ref()),
+            ("message_update_mention_removed_msg", self.message_update_mention_removed_msg.as_ref()),
+            ("message_update_mention_role_added_msg", self.message_update_mention_role_added_msg.as_ref()),
+            ("message_update_mention_role_removed_msg", self.message_update_mention_role_removed_msg.as_ref()),
+            ("message_update_attachment_added_msg", self.message_update_attachment_added_msg.as_ref()),
+            ("message_update_attachment_removed_msg", self.message_update_attachment_removed_msg.as_ref()),
+            ("message_update_embeds_added_msg", self.message_update_embeds_added_msg.as_ref()),
+            ("message_update_embeds_removed_msg", self.message_update_embeds_removed_msg.as_ref()),
+            ("message_delete_cached_msg", self.message_delete_cached_msg.as_ref()),
+            ("message_delete_uncached_msg", self.message_delete_uncached_msg.as_ref()),
+            ("message_delete_bulk_msg", self.message_delete_bulk_msg.as_ref()),
+            ("server_member_add_msg", self.server_member_add_msg.as_ref()),
+            ("server_member_role_add_msg", self.server_member_role_add_msg.as_ref()),
+            ("server_member_role_remove_msg", self.server_member_role_remove_msg.as_ref()),
+            ("server_member_nick_change_msg", self.server_member_nick_change_msg.as_ref()),
+            ("server_member_no_change_msg", self.server_member_no_change_msg.as_ref()),
+            ("server_member_remove_msg", self.server_member_remove_msg.as_ref()),
+            ("server_role_create_msg", self.server_role_create_msg.as_ref()),
+            ("server_ban_add_msg", self.server_ban_add_msg.as_ref()),
+            ("server_ban_remove_msg", self.server_ban_remove_msg.as_ref()),
+            ("server_emoji_add_msg", self.server_emoji_add_msg.as_ref()),
+            ("server_emoji_remove_msg", self.server_emoji_remove_msg.as_ref()),
+            ("server_emoji_name_change_msg", self.server_emoji_name_change_msg.as_ref()),
+            ("channel_create_msg", self.channel_create_msg.as_ref()),
+            ("channel_update_no_change_msg", self.channel_update_no_change_msg.as_ref()),
+            ("channel_update_name_msg", self.channel_update_name_msg.as_ref()),
+            ("channel_update_user_overwrite_add_msg", self.channel_update_user_overwrite_add_msg.as_ref()),
+            ("channel_update_user_overwrite_remove_msg", self.channel_update_user_overwrite_remove_msg.as_ref()),
+            ("channel_update_role_overwrite_add_msg", self.channel_update_role_overwrite_add_msg.as_ref()),
+            ("channel_update_role_overwrite_remove_msg", self.channel_update_role_overwrite_remove_msg.as_ref()),
+            ("channel_update_user_perms_msg", self.channel_update_user_perms_msg.as_ref()),
+            ("channel_update_role_perms_msg", self.channel_update_role_perms_msg.as_ref()),
+            ("channel_update_topic_msg", self.channel_update_topic_msg.as_ref()),
+            ("channel_update_position_msg", self.channel_update_position_msg.as_ref()),
+            ("channel_update_bitrate_msg", self.channel_update_bitrate_msg.as_ref()),
+            ("channel_update_user_limit_msg", self.channel_update_user_limit_msg.as_ref()),
+            ("channel_update_role_gained_msg", self.channel_update_role_gained_msg.as_ref()),
+            ("channel_update_role_lost_msg", self.channel_update_role_lost_msg.as_ref()),
+            ("channel_update_user_gained_msg", self.channel_update_user_gained_msg.as_ref()),
+            ("channel_update_user_lost_msg", self.channel_update_user_lost_msg.as_ref()),
+            ("channel_delete_msg", self.channel_delete_msg.as_ref()),
+            ("reaction_add_cached_msg", self.reaction_add_cached_msg.as_ref()),
+            ("reaction_add_uncached_msg", self.reaction_add_uncached_msg.as_ref()),
+            ("reaction_remove_cached_msg", self.reaction_remove_cached_msg.as_ref()),
+            ("reaction_remove_uncached_msg", self.reaction_remove_uncached_msg.as_ref()),
+        ]
+    }
+
+    /// Checks every template for malformed braces and placeholders the event doesn't provide.
+    /// Returns a description of every problem found.
+    pub fn check_templates(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (field, template) in self.templates() {
+            let template = match template {
+                Some(template) => template,
+                None => continue,
+            };
+            let keys = match parse(template) {
+                Ok(keys) => keys,
+                Err(err) => {
+                    problems.push(format!("{}: {}", field, err));
+                    continue;
+                }
+            };
+            let known = placeholders(field);
+            for key in keys {
+                if !known.iter().any(|k| *k == key) {
+                    problems.push(format!("{}: unknown placeholder `{{{}}}`", field, key));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Renders every template with made up values, `None` for disabled templates.
+    pub fn render_samples(&self) -> Vec<(&'static str, Option<String>)> {
+        self.templates().into_iter().map(|(field, template)| {
+            let map: HashMap<_, _> = placeholders(field).into_iter()
+                .map(|key| {
+                    let value = sample_value(&key);
+                    (key, value)
+                })
+                .collect();
+            let rendered = template.map(|template| match strfmt(template, &map) {
+                Ok(msg) => msg,
+                Err(err) => format!("error: {}", err),
+            });
+            (field, rendered)
+        }).collect()
+    }
+}
+
+/// Returns a plausible value for a placeholder.
+fn sample_value(key: &str) -> String {
+    let last = key.rsplit('_').next().unwrap();
+    let value = match last {
+        "id" => "123456789012345678",
+        "name" => "example",
+        "discriminator" => "1234",
+        "nick" => "nick",
+        "content" => "Hello world",
+        "from" => "old",
+        "to" => "new",
+        "diff" => "~~old~~ **new**",
+        "time" => "2017-01-01T12:00:00+00:00",
+        "count" => "3",
+        "permission" => "SendMessages",
+        "permissions" => "read_messages, send_messages",
+        "type" => "member",
+        "Type" => "Member",
+        "url" => "https://example.com/file.png",
+        _ => return format!("<{}>", key),
+    };
+    value.to_string()
+}
+
+/// Returns the placeholders used in a template, the same way `strfmt` parses it.
+///
+/// `{{` and `}}` are escaped braces, everything after a `:` within a placeholder is its format spec.
+pub fn parse(template: &str) -> Result<Vec<String>, String> {
+    let mut keys = Vec::new();
+    let mut chars = template.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '{' if chars.peek().map(|&(_, c)| c == '{').unwrap_or(false) => {
+                chars.next();
+            },
+            '{' => {
+                let mut key = String::new();
+                let mut closed = false;
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '}' => {
+                            closed = true;
+                            break;
+                        },
+                        '{' => return Err(format!("`{{` within placeholder starting at {}", pos)),
+                        c => key.push(c),
+                    }
+                }
+                if !closed {
+                    return Err(format!("unclosed `{{` at {}", pos));
+                }
+                let key = key.split(':').next().unwrap().to_string();
+                if key.is_empty() {
+                    return Err(format!("empty placeholder at {}", pos));
+                }
+                keys.push(key);
+            },
+            '}' if chars.peek().map(|&(_, c)| c == '}').unwrap_or(false) => {
+                chars.next();
+            },
+            '}' => return Err(format!("unmatched `}}` at {}", pos)),
+            _ => {},
+        }
+    }
+    Ok(keys)
+}
+
+/// Returns the placeholders the event of the given template field provides.
+///
+/// This must be kept in sync with the handlers and the `MergeIntoMap` implementations.
+pub fn placeholders(field: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    {
+        let k = &mut keys;
+        match field {
+            "message_create_msg" | "message_delete_cached_msg" => message(k, ""),
+            "message_update_uncached_msg" => message_update(k, ""),
+            "message_delete_uncached_msg" => add(k, "", &["channel_id", "message_id"]),
+            "message_delete_bulk_msg" => add(k, "", &["channel_id", "count"]),
+            _ if field.starts_with("message_update_") => {
+                user(k, "cached_author_");
+                user(k, "author_");
+                add(k, "", &["message_id", "channel_id"]);
+                match field {
+                    "message_update_content_msg" | "message_update_content_diff_msg" =>
+                        add(k, "", &["from", "to", "diff"]),
+                    "message_update_pinned_msg" | "message_update_unpinned_msg" => {},
+                    "message_update_mention_added_msg" | "message_update_mention_removed_msg" => user(k, ""),
+                    "message_update_mention_role_added_msg" | "message_update_mention_role_removed_msg" =>
+                        add(k, "", &["id"]),
+                    "message_update_attachment_added_msg" | "message_update_attachment_removed_msg" =>
+                        attachment(k, ""),
+                    "message_update_embeds_added_msg" | "message_update_embeds_removed_msg" => add(k, "", &["value"]),
+                    _ => add(k, "", &["from", "to"]),
+                }
+            },
+            "server_member_add_msg" => member(k, ""),
+            "server_member_role_add_msg" | "server_member_role_remove_msg" => {
+                role(k, "role_");
+                member(k, "member_");
+            },
+            "server_member_nick_change_msg" => {
+                member(k, "member_");
+                add(k, "", &["from", "to"]);
+            },
+            "server_member_no_change_msg" => member(k, "member_"),
+            "server_member_remove_msg" | "server_ban_add_msg" | "server_ban_remove_msg" => user(k, ""),
+            "server_role_create_msg" => role(k, ""),
+            "server_emoji_add_msg" | "server_emoji_remove_msg" => emoji(k, "emoji_"),
+            "server_emoji_name_change_msg" => add(k, "", &["emoji_id", "from", "to"]),
+            "channel_create_msg" | "channel_delete_msg" => channel(k, ""),
+            _ if field.starts_with("channel_update_") => {
+                add(k, "", &["channel_id", "channel_name"]);
+                match field {
+                    "channel_update_no_change_msg" => {},
+                    "channel_update_user_overwrite_add_msg" | "channel_update_user_overwrite_remove_msg" =>
+                        add(k, "", &["user_id"]),
+                    "channel_update_role_overwrite_add_msg" | "channel_update_role_overwrite_remove_msg" =>
+                        add(k, "", &["role_id"]),
+                    "channel_update_user_perms_msg" => add(k, "", &["user_id", "permission", "from", "to"]),
+                    "channel_update_role_perms_msg" => add(k, "", &["role_id", "permission", "from", "to"]),
+                    "channel_update_role_gained_msg" | "channel_update_role_lost_msg" =>
+                        add(k, "", &["role_id", "role_name", "permissions"]),
+                    "channel_update_user_gained_msg" | "channel_update_user_lost_msg" => {
+                        member(k, "user_");
+                        add(k, "", &["permissions"]);
+                    },
+                    _ => add(k, "", &["from", "to"]),
+                }
+            },
+            _ if field.starts_with("reaction_") => {
+                reaction(k, "");
+                member(k, "user_");
+                channel(k, "channel_");
+                if field == "reaction_add_cached_msg" || field == "reaction_remove_cached_msg" {
+                    message(k, "message_");
+                }
+            },
+            _ => {},
+        }
+    }
+    keys
+}
+
+fn add(keys: &mut Vec<String>, prefix: &str, names: &[&str]) {
+    keys.extend(names.iter().map(|name| format!("{}{}", prefix, name)));
+}
+
+fn user(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["id", "name", "discriminator", "avatar", "type", "Type"]);
+}
+
+fn member(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["roles", "nick", "time", "mute", "deaf"]);
+    user(keys, prefix);
+}
+
+fn message(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["id", "channel_id", "content", "nonce", "tts", "time", "edited_time", "pinned",
+        "mention_everyone", "mentions", "mention_roles", "reactions", "attachments", "embeds"]);
+    user(keys, &format!("{}author_", prefix));
+}
+
+fn message_update(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["debug", "id", "channel_id", "kind", "content", "nonce", "tts", "pinned", "time",
+        "edited_time", "mention_everyone", "mentions", "mention_roles", "attachments", "embeds"]);
+    user(keys, &format!("{}author_", prefix));
+}
+
+fn attachment(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["id", "filename", "url", "proxy_url", "size", "width", "height"]);
+}
+
+fn role(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["id", "name", "color", "hoist", "managed", "position", "mentionable", "perms"]);
+    add(keys, &format!("{}perm_", prefix), &["add_reactions", "administrator", "attach_files", "ban_members",
+        "change_nicknames", "create_invite", "embed_links", "external_emojis", "kick_members", "manage_channels",
+        "manage_emojis", "manage_messages", "manage_nicknames", "manage_roles", "manage_server",
+        "manage_webhooks", "mention_everyone", "read_history", "read_messages", "send_messages",
+        "send_tts_messages", "voice_connect", "voice_deafen_members", "voice_move_members",
+        "voice_mute_members", "voice_speak", "voice_use_vad"]);
+}
+
+fn emoji(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["id", "name", "managed", "require_colons", "roles"]);
+}
+
+fn channel(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["id", "name", "Type", "perms", "topic", "position", "last_message_id", "bitrate",
+        "user_limit", "last_pin_time"]);
+}
+
+fn reaction(keys: &mut Vec<String>, prefix: &str) {
+    add(keys, prefix, &["channel_id", "message_id", "user_id"]);
+    add(keys, &format!("{}emoji_", prefix), &["name", "id"]);
+}
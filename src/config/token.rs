@@ -0,0 +1,95 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use atty;
+
+use errors::*;
+use config::{Config, BotConfig};
+
+/// Environment variable holding the bot token.
+pub const TOKEN_ENV: &'static str = "DILOBO_TOKEN";
+/// Environment variable holding the path of a file containing the bot token.
+pub const TOKEN_FILE_ENV: &'static str = "DILOBO_TOKEN_FILE";
+
+impl Config {
+    /// Determines the bot token and stores it in the `bot` section.
+    ///
+    /// The first token found is used, in this order: `--token`, the file given by `--token-file`,
+    /// `DILOBO_TOKEN`, the file given by `DILOBO_TOKEN_FILE`, the file given by `token_file`
+    /// in the config, `token` in the config.
+    /// If there is none, it is asked for if stdin is a terminal. An entered token is only saved
+    /// if `token_file` is set in the config and the file doesn't exist yet.
+    pub fn resolve_token(&mut self, token: Option<String>, token_file: Option<String>) -> Result<()> {
+        let config_token_file = self.bot.as_ref().and_then(|b| b.token_file.clone());
+        let config_token = self.bot.as_ref().and_then(|b| b.token.clone());
+        let interactive = atty::is(atty::Stream::Stdin);
+        let token = if let Some(token) = token {
+            token
+        } else if let Some(file) = token_file {
+            read_token_file(&file)?
+        } else if let Ok(token) = env::var(TOKEN_ENV) {
+            token
+        } else if let Ok(file) = env::var(TOKEN_FILE_ENV) {
+            read_token_file(&file)?
+        } else if let Some(file) = config_token_file {
+            if interactive && !Path::new(&file).exists() {
+                let token = prompt_token();
+                write_token_file(&file, &token)?;
+                token
+            } else {
+                read_token_file(&file)?
+            }
+        } else if let Some(token) = config_token {
+            token
+        } else if interactive {
+            println!("The token won't be saved, set `token_file` in the [bot] section of the config to keep it.");
+            prompt_token()
+        } else {
+            return Err(ErrorKind::ConfigError(format!(
+                "No bot token given, set {} or {}, or give it in the config", TOKEN_ENV, TOKEN_FILE_ENV)).into());
+        };
+        match self.bot {
+            Some(ref mut bot) => bot.token = Some(token),
+            None => self.bot = Some(BotConfig {
+                token: Some(token),
+                token_file: None,
+                log_flush_interval_ms: None,
+                config_poll_interval_ms: None,
+            }),
+        }
+        Ok(())
+    }
+}
+
+fn read_token_file(path: &str) -> Result<String> {
+    let mut token = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut token))
+        .chain_err(|| format!("Failed to read token file {}", path))?;
+    Ok(token.trim().to_string())
+}
+
+/// Saves an entered token to a new file which only the current user can read.
+fn write_token_file(path: &str, token: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).and_then(|mut file| writeln!(file, "{}", token))
+        .chain_err(|| format!("Failed to write token file {}", path))?;
+    println!("Saved the token to {}", path);
+    Ok(())
+}
+
+fn prompt_token() -> String {
+    print!("Please insert the bot token: ");
+    io::stdout().flush().expect("could not flush stdout");
+    let stdin = io::stdin();
+    let mut token = String::new();
+    stdin.read_line(&mut token).expect("could not read from stdin");
+    token.trim().to_string()
+}
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use std::fs;
+use std::io::{self, Write};
+
+use config::Config;
+
+/// Watches the config file and reloads it whenever it is modified.
+///
+/// A modified config only replaces the current one if it can be read and is valid,
+/// otherwise the error is reported and the previous config stays active.
+/// Every successful reload increments the generation of the config.
+#[derive(Clone)]
+pub struct ConfigWatcher {
+    current: Arc<Mutex<(u64, Config)>>,
+    listeners: Arc<Mutex<Vec<Box<Fn() -> bool + Send>>>>,
+}
+
+impl ConfigWatcher {
+    /// Starts polling the file at `path` every `interval`, beginning with `config` as generation 0.
+    pub fn new<P: Into<PathBuf>>(path: P, config: Config, interval: Duration) -> ConfigWatcher {
+        let watcher = ConfigWatcher {
+            current: Arc::new(Mutex::new((0, config))),
+            listeners: Arc::new(Mutex::new(Vec::new())),
+        };
+        let path = path.into();
+        let current = watcher.current.clone();
+        let listeners = watcher.listeners.clone();
+        thread::spawn(move || watch(&path, current, listeners, interval));
+        watcher
+    }
+
+    /// Returns the generation and the most recent valid config.
+    pub fn current(&self) -> (u64, Config) {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Returns the current config if its generation is newer than `seen`, and updates `seen`.
+    pub fn changed(&self, seen: &mut u64) -> Option<Config> {
+        let current = self.current.lock().unwrap();
+        if current.0 == *seen {
+            return None;
+        }
+        *seen = current.0;
+        Some(current.1.clone())
+    }
+
+    /// Calls `listener` from the watching thread after every reload, until it returns false.
+    pub fn on_reload<F: Fn() -> bool + Send + 'static>(&self, listener: F) {
+        self.listeners.lock().unwrap().push(Box::new(listener));
+    }
+}
+
+fn watch(path: &Path, current: Arc<Mutex<(u64, Config)>>, listeners: Arc<Mutex<Vec<Box<Fn() -> bool + Send>>>>,
+        interval: Duration) {
+    let mut modified = mtime(path);
+    loop {
+        thread::sleep(interval);
+        let now = mtime(path);
+        if now == modified {
+            continue;
+        }
+        modified = now;
+        let res = Config::load(path).and_then(|config| {
+            config.check()?;
+            Ok(config)
+        });
+        let mut config = match res {
+            Ok(config) => config,
+            Err(err) => {
+                writeln!(io::stderr(), "error: not reloading config: {}", err).expect("failed writing to stderr");
+                continue;
+            }
+        };
+        {
+            let mut current = current.lock().unwrap();
+            // the token is only determined on startup and changes to [bot] need a restart anyway
+            config.bot = current.1.bot.clone();
+            current.0 += 1;
+            current.1 = config;
+        }
+        println!("Reloaded config");
+        listeners.lock().unwrap().retain(|listener| listener());
+    }
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -23,6 +23,10 @@ error_chain! {
             description("format error")
             display("format error for server {}: {:?}", server, err)
         }
+        RestartLimitReached(retries: u32) {
+            description("restart limit reached")
+            display("giving up after {} consecutive failures", retries)
+        }
     }
 }
 
@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use discord::{Error, Result};
+use discord::model::{
+    Event,
+    ReadyEvent,
+    OnlineStatus,
+    ChannelType,
+    ServerId,
+    ChannelId,
+    UserId,
+    PublicChannel,
+    PermissionOverwrite,
+};
+
+use gateway::{Gateway, RestClient};
+
+/// A message which was sent through `FakeRest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sent {
+    pub channel: ChannelId,
+    pub text: String,
+    /// Name and content of an uploaded file
+    pub file: Option<(String, String)>,
+}
+
+/// Offline replacement for discord.
+///
+/// Connecting replays a script of events, which must start with `Ready`.
+/// All messages sent are recorded instead. Created channels, including private ones,
+/// get new ids and are not announced by a `ChannelCreate` event.
+pub struct FakeRest {
+    script: Mutex<VecDeque<Event>>,
+    sent: Mutex<Vec<Sent>>,
+    next_id: Mutex<u64>,
+}
+
+impl FakeRest {
+    pub fn new(script: Vec<Event>) -> FakeRest {
+        FakeRest {
+            script: Mutex::new(script.into_iter().collect()),
+            sent: Mutex::new(Vec::new()),
+            // far above the ids used in fixtures
+            next_id: Mutex::new(1000000),
+        }
+    }
+
+    /// Returns all messages sent so far.
+    pub fn sent(&self) -> Vec<Sent> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    fn next_id(&self) -> ChannelId {
+        let mut next_id = self.next_id.lock().unwrap();
+        *next_id += 1;
+        ChannelId(*next_id - 1)
+    }
+}
+
+impl RestClient for FakeRest {
+    fn connect(&self) -> Result<(Box<Gateway>, ReadyEvent)> {
+        let mut script = self.script.lock().unwrap();
+        match script.pop_front() {
+            Some(Event::Ready(ready)) => {
+                let events = script.drain(..).collect();
+                Ok((Box::new(FakeGateway { events: events }), ready))
+            },
+            Some(_) => Err(Error::Other("script must start with a Ready event")),
+            None => Err(Error::Other("script is exhausted")),
+        }
+    }
+
+    fn send_message(&self, channel: ChannelId, text: &str) -> Result<()> {
+        self.sent.lock().unwrap().push(Sent {
+            channel: channel,
+            text: text.to_string(),
+            file: None,
+        });
+        Ok(())
+    }
+
+    fn send_file(&self, channel: ChannelId, text: &str, content: &[u8], filename: &str) -> Result<()> {
+        self.sent.lock().unwrap().push(Sent {
+            channel: channel,
+            text: text.to_string(),
+            file: Some((filename.to_string(), String::from_utf8_lossy(content).into_owned())),
+        });
+        Ok(())
+    }
+
+    fn create_channel(&self, server: ServerId, name: &str) -> Result<PublicChannel> {
+        Ok(PublicChannel {
+            id: self.next_id(),
+            name: name.to_string(),
+            server_id: server,
+            kind: ChannelType::Text,
+            permission_overwrites: Vec::new(),
+            topic: None,
+            position: 0,
+            last_message_id: None,
+            bitrate: None,
+            user_limit: None,
+            last_pin_timestamp: None,
+        })
+    }
+
+    fn create_permission(&self, _channel: ChannelId, _overwrite: PermissionOverwrite) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_channel(&self, _channel: ChannelId) -> Result<()> {
+        Ok(())
+    }
+
+    fn create_private_channel(&self, _user: UserId) -> Result<ChannelId> {
+        Ok(self.next_id())
+    }
+}
+
+struct FakeGateway {
+    events: VecDeque<Event>,
+}
+
+impl Gateway for FakeGateway {
+    fn recv_event(&mut self) -> Result<Option<Event>> {
+        Ok(self.events.pop_front())
+    }
+
+    fn set_presence(&mut self, _status: OnlineStatus) {}
+
+    fn closer(&self) -> Option<Box<Fn() + Send>> {
+        // receiving never blocks
+        None
+    }
+}
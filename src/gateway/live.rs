@@ -0,0 +1,73 @@
+use discord::{Discord, Connection, Error, Result};
+use discord::model::{
+    Event,
+    ReadyEvent,
+    OnlineStatus,
+    ServerId,
+    ChannelId,
+    UserId,
+    Channel,
+    ChannelType,
+    PublicChannel,
+    PermissionOverwrite,
+};
+
+use gateway::{Gateway, RestClient};
+
+/// Connection to the real discord, using discord-rs.
+pub struct DiscordRest(Discord);
+
+impl DiscordRest {
+    pub fn from_bot_token(token: &str) -> Result<DiscordRest> {
+        Ok(DiscordRest(Discord::from_bot_token(token)?))
+    }
+}
+
+impl RestClient for DiscordRest {
+    fn connect(&self) -> Result<(Box<Gateway>, ReadyEvent)> {
+        let (con, ready) = self.0.connect()?;
+        Ok((Box::new(con), ready))
+    }
+
+    fn send_message(&self, channel: ChannelId, text: &str) -> Result<()> {
+        self.0.send_message(channel, text, "", false).map(|_| ())
+    }
+
+    fn send_file(&self, channel: ChannelId, text: &str, content: &[u8], filename: &str) -> Result<()> {
+        self.0.send_file(channel, text, content, filename).map(|_| ())
+    }
+
+    fn create_channel(&self, server: ServerId, name: &str) -> Result<PublicChannel> {
+        match self.0.create_channel(server, name, ChannelType::Text)? {
+            Channel::Public(channel) => Ok(channel),
+            _ => Err(Error::Other("created channel is not a server channel")),
+        }
+    }
+
+    fn create_permission(&self, channel: ChannelId, overwrite: PermissionOverwrite) -> Result<()> {
+        self.0.create_permission(channel, overwrite)
+    }
+
+    fn delete_channel(&self, channel: ChannelId) -> Result<()> {
+        self.0.delete_channel(channel).map(|_| ())
+    }
+
+    fn create_private_channel(&self, user: UserId) -> Result<ChannelId> {
+        self.0.create_private_channel(user).map(|channel| channel.id)
+    }
+}
+
+impl Gateway for Connection {
+    fn recv_event(&mut self) -> Result<Option<Event>> {
+        Connection::recv_event(self).map(Some)
+    }
+
+    fn set_presence(&mut self, status: OnlineStatus) {
+        Connection::set_presence(self, None, status, false)
+    }
+
+    fn closer(&self) -> Option<Box<Fn() + Send>> {
+        // discord-rs only closes the connection when it is dropped
+        None
+    }
+}
@@ -0,0 +1,54 @@
+mod live;
+mod fake;
+mod record;
+
+pub use self::live::DiscordRest;
+pub use self::fake::{FakeRest, Sent};
+pub use self::record::RecordingRest;
+
+use discord::Result;
+use discord::model::{
+    Event,
+    ReadyEvent,
+    OnlineStatus,
+    ServerId,
+    ChannelId,
+    UserId,
+    PublicChannel,
+    PermissionOverwrite,
+};
+
+// The bot only talks to discord through these traits, so it can be run against
+// the real discord as well as against a scripted offline replacement.
+
+/// Receiving side of a gateway session.
+///
+/// Events are received on a separate thread.
+pub trait Gateway: Send {
+    /// Blocks until the next event is received.
+    ///
+    /// Returns `None` if a scripted session has no events left. A connection to discord never
+    /// ends this way, even if discord closes it regularly, as the bot has to reconnect then.
+    fn recv_event(&mut self) -> Result<Option<Event>>;
+    fn set_presence(&mut self, status: OnlineStatus);
+    /// Returns a handle which closes the connection from another thread, so a blocked
+    /// `recv_event` returns with an error. `None` if the connection can't be closed that way.
+    fn closer(&self) -> Option<Box<Fn() + Send>>;
+}
+
+/// Requests sent to discord.
+///
+/// This is shared between the event loop and the log queue's thread.
+pub trait RestClient: Send + Sync {
+    /// Opens a new gateway session.
+    fn connect(&self) -> Result<(Box<Gateway>, ReadyEvent)>;
+    fn send_message(&self, channel: ChannelId, text: &str) -> Result<()>;
+    fn send_file(&self, channel: ChannelId, text: &str, content: &[u8], filename: &str) -> Result<()>;
+    /// Creates a text channel on the server.
+    fn create_channel(&self, server: ServerId, name: &str) -> Result<PublicChannel>;
+    /// Sets the permissions of a role or member in the channel.
+    fn create_permission(&self, channel: ChannelId, overwrite: PermissionOverwrite) -> Result<()>;
+    fn delete_channel(&self, channel: ChannelId) -> Result<()>;
+    /// Returns the channel for direct messages with the user.
+    fn create_private_channel(&self, user: UserId) -> Result<ChannelId>;
+}
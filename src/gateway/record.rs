@@ -0,0 +1,615 @@
+use std::borrow::Cow;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use flate2::read::ZlibDecoder;
+use serde_json::Value;
+use discord_json;
+use websocket::client::{Client, Sender, Receiver};
+use websocket::client::request::Url;
+use websocket::message::{Message, Type};
+use websocket::stream::WebSocketStream;
+use websocket::ws::sender::Sender as WsSender;
+use websocket::ws::receiver::Receiver as WsReceiver;
+use discord::{Error, Result};
+use discord::model::{
+    Event,
+    GatewayEvent,
+    ReadyEvent,
+    OnlineStatus,
+    ServerId,
+    ChannelId,
+    UserId,
+    PublicChannel,
+    PermissionOverwrite,
+};
+
+use gateway::{Gateway, RestClient};
+
+const GATEWAY_URL: &'static str = "wss://gateway.discord.gg/?v=6&encoding=json";
+/// Number of times a dropped connection is resumed in a row before the bot has to start a new session.
+const RESUME_ATTEMPTS: u32 = 5;
+
+/// Wraps a client and appends every event discord sends to a file.
+///
+/// discord-rs only hands out decoded events, so the gateway connection is made here instead.
+/// That way every dispatch is written exactly as it was received, one per line, including
+/// events the bot doesn't handle and ones discord-rs can't decode.
+/// They can be replayed with `replay::load_events`. Requests are sent through the inner client.
+pub struct RecordingRest {
+    inner: Arc<RestClient>,
+    token: String,
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl RecordingRest {
+    pub fn new<P: AsRef<Path>>(inner: Arc<RestClient>, token: &str, path: P) -> io::Result<RecordingRest> {
+        // append, so restarts of the bot don't overwrite what was recorded before
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(RecordingRest {
+            inner: inner,
+            token: token.to_string(),
+            recorder: Arc::new(Mutex::new(Recorder { out: Box::new(file) })),
+        })
+    }
+}
+
+impl RestClient for RecordingRest {
+    fn connect(&self) -> Result<(Box<Gateway>, ReadyEvent)> {
+        let (con, ready) = RecordingGateway::connect(Box::new(WebsocketConnector), &self.token,
+            self.recorder.clone(), Delays::default())?;
+        Ok((Box::new(con), ready))
+    }
+
+    fn send_message(&self, channel: ChannelId, text: &str) -> Result<()> {
+        self.inner.send_message(channel, text)
+    }
+
+    fn send_file(&self, channel: ChannelId, text: &str, content: &[u8], filename: &str) -> Result<()> {
+        self.inner.send_file(channel, text, content, filename)
+    }
+
+    fn create_channel(&self, server: ServerId, name: &str) -> Result<PublicChannel> {
+        self.inner.create_channel(server, name)
+    }
+
+    fn create_permission(&self, channel: ChannelId, overwrite: PermissionOverwrite) -> Result<()> {
+        self.inner.create_permission(channel, overwrite)
+    }
+
+    fn delete_channel(&self, channel: ChannelId) -> Result<()> {
+        self.inner.delete_channel(channel)
+    }
+
+    fn create_private_channel(&self, user: UserId) -> Result<ChannelId> {
+        self.inner.create_private_channel(user)
+    }
+}
+
+/// Opens connections to the gateway.
+///
+/// The session only talks to the websocket through these traits, so it can be tested without discord.
+trait Connector: Send {
+    fn open(&self) -> Result<(Box<FrameSender>, Box<FrameReceiver>)>;
+}
+
+trait FrameSender: Send {
+    fn send_frame(&mut self, frame: &str) -> Result<()>;
+    /// Closes the connection in both directions, so a blocked receive returns.
+    fn close(&mut self);
+}
+
+trait FrameReceiver: Send {
+    /// Blocks until the next frame with a payload is received and returns its text.
+    fn recv_frame(&mut self) -> Result<String>;
+}
+
+/// Waiting times of a session.
+#[derive(Debug, Copy, Clone)]
+struct Delays {
+    /// Before resuming again after a failed attempt, doubled for every further attempt.
+    resume: Duration,
+    /// Before identifying again after discord invalidated the session, as discord asks for.
+    identify: Duration,
+}
+
+impl Default for Delays {
+    fn default() -> Delays {
+        Delays {
+            resume: Duration::from_secs(1),
+            identify: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Gateway session which records the dispatches it receives.
+///
+/// If the connection drops, the session is resumed on a new connection. That is tried
+/// `RESUME_ATTEMPTS` times in a row before the error is passed on, unless the session was closed.
+struct RecordingGateway {
+    connector: Box<Connector>,
+    token: String,
+    session_id: Option<String>,
+    /// Sequence number of the last dispatch, shared with the keepalive thread.
+    seq: Arc<Mutex<Option<u64>>>,
+    sender: Arc<Mutex<Box<FrameSender>>>,
+    receiver: Box<FrameReceiver>,
+    /// Dropping this stops the keepalive thread.
+    keepalive: Option<mpsc::Sender<()>>,
+    recorder: Arc<Mutex<Recorder>>,
+    delays: Delays,
+    /// Set once the session is closed by the bot, it isn't resumed afterwards.
+    closed: Arc<AtomicBool>,
+}
+
+impl RecordingGateway {
+    fn connect(connector: Box<Connector>, token: &str, recorder: Arc<Mutex<Recorder>>, delays: Delays)
+            -> Result<(RecordingGateway, ReadyEvent)> {
+        let (sender, receiver) = connector.open()?;
+        let mut con = RecordingGateway {
+            connector: connector,
+            token: token.to_string(),
+            session_id: None,
+            seq: Arc::new(Mutex::new(None)),
+            sender: Arc::new(Mutex::new(sender)),
+            receiver: receiver,
+            keepalive: None,
+            recorder: recorder,
+            delays: delays,
+            closed: Arc::new(AtomicBool::new(false)),
+        };
+        con.identify()?;
+        match con.recv()? {
+            Event::Ready(ready) => Ok((con, ready)),
+            _ => Err(Error::Protocol("expected Ready during handshake")),
+        }
+    }
+
+    /// Receives frames until the next dispatch, handling everything else on the way.
+    fn recv(&mut self) -> Result<Event> {
+        loop {
+            let frame = self.receiver.recv_frame()?;
+            let value: discord_json::Value = discord_json::from_str(&frame)?;
+            if value.find("op").and_then(|op| op.as_u64()) == Some(0) {
+                self.recorder.lock().unwrap().write(&frame);
+            }
+            match GatewayEvent::decode(value)? {
+                GatewayEvent::Dispatch(seq, evt) => {
+                    *self.seq.lock().unwrap() = Some(seq);
+                    if let Event::Ready(ref ready) = evt {
+                        self.session_id = Some(ready.session_id.clone());
+                    }
+                    return Ok(evt);
+                },
+                GatewayEvent::Heartbeat(_) => {
+                    let seq = *self.seq.lock().unwrap();
+                    self.send(&heartbeat(seq))?;
+                },
+                GatewayEvent::HeartbeatAck => {},
+                GatewayEvent::Hello(interval) => self.start_keepalive(interval),
+                GatewayEvent::Reconnect => self.resume()?,
+                GatewayEvent::InvalidateSession => {
+                    self.session_id = None;
+                    *self.seq.lock().unwrap() = None;
+                    thread::sleep(self.delays.identify);
+                    self.identify()?;
+                },
+            }
+        }
+    }
+
+    fn send(&self, value: &Value) -> Result<()> {
+        self.sender.lock().unwrap().send_frame(&value.to_string())
+    }
+
+    fn identify(&self) -> Result<()> {
+        self.send(&json!({
+            "op": 2,
+            "d": {
+                "token": self.token,
+                "properties": {
+                    "$os": ::std::env::consts::OS,
+                    "$browser": "dilobo",
+                    "$device": "dilobo",
+                    "$referring_domain": "",
+                    "$referrer": "",
+                },
+                "large_threshold": 250,
+                "compress": false,
+            },
+        }))
+    }
+
+    /// Opens a new connection and resumes the session on it, or starts a new one
+    /// if there is no session yet.
+    fn resume(&mut self) -> Result<()> {
+        let (sender, receiver) = self.connector.open()?;
+        // the keepalive thread picks up the new connection
+        *self.sender.lock().unwrap() = sender;
+        self.receiver = receiver;
+        let session_id = match self.session_id.clone() {
+            Some(session_id) => session_id,
+            None => return self.identify(),
+        };
+        let seq = *self.seq.lock().unwrap();
+        self.send(&json!({
+            "op": 6,
+            "d": {
+                "token": self.token,
+                "session_id": session_id,
+                "seq": seq,
+            },
+        }))
+    }
+
+    fn start_keepalive(&mut self, interval: u64) {
+        let (stop, stopped) = mpsc::channel();
+        // replacing the old channel stops the old thread
+        self.keepalive = Some(stop);
+        let sender = self.sender.clone();
+        let seq = self.seq.clone();
+        thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(Duration::from_millis(interval)) {
+                let seq = *seq.lock().unwrap();
+                // a dead connection is noticed by the receiving side
+                let _ = sender.lock().unwrap().send_frame(&heartbeat(seq).to_string());
+            }
+        });
+    }
+}
+
+impl Gateway for RecordingGateway {
+    fn recv_event(&mut self) -> Result<Option<Event>> {
+        let mut attempt = 0;
+        let mut delay = self.delays.resume;
+        loop {
+            let res = if attempt == 0 {
+                self.recv()
+            } else {
+                self.resume().and_then(|()| self.recv())
+            };
+            let err = match res {
+                Ok(evt) => return Ok(Some(evt)),
+                // a single frame which can't be decoded doesn't affect the connection
+                Err(err @ Error::Decode(..)) | Err(err @ Error::Json(..)) => return Err(err),
+                Err(err) => err,
+            };
+            if attempt == RESUME_ATTEMPTS || self.closed.load(Ordering::SeqCst) {
+                // the bot starts a new session then, if it still wants one
+                return Err(err);
+            }
+            writeln!(io::stderr(), "error: gateway connection dropped, resuming: {:?}", err)
+                .expect("failed writing to stderr");
+            if attempt > 0 {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            attempt += 1;
+        }
+    }
+
+    fn set_presence(&mut self, status: OnlineStatus) {
+        let res = self.send(&json!({
+            "op": 3,
+            "d": {
+                "since": 0,
+                "game": null,
+                "afk": false,
+                "status": status.name(),
+            },
+        }));
+        if let Err(err) = res {
+            writeln!(io::stderr(), "error: could not set presence: {:?}", err).expect("failed writing to stderr");
+        }
+    }
+
+    fn closer(&self) -> Option<Box<Fn() + Send>> {
+        let closed = self.closed.clone();
+        // a resumed connection replaces the one in the sender, so always the current one is closed
+        let sender = self.sender.clone();
+        Some(Box::new(move || {
+            closed.store(true, Ordering::SeqCst);
+            sender.lock().unwrap().close();
+        }))
+    }
+}
+
+/// Connects to discord's gateway.
+struct WebsocketConnector;
+
+impl Connector for WebsocketConnector {
+    fn open(&self) -> Result<(Box<FrameSender>, Box<FrameReceiver>)> {
+        let url = Url::parse(GATEWAY_URL).map_err(|_| Error::Other("invalid gateway url"))?;
+        let response = Client::connect(url)?.send()?;
+        response.validate()?;
+        let (sender, receiver) = response.begin().split();
+        Ok((Box::new(sender), Box::new(receiver)))
+    }
+}
+
+impl FrameSender for Sender<WebSocketStream> {
+    fn send_frame(&mut self, frame: &str) -> Result<()> {
+        self.send_message(&Message::text(frame.to_string()))?;
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        // the connection is gone either way
+        let _ = self.shutdown_all();
+    }
+}
+
+impl FrameReceiver for Receiver<WebSocketStream> {
+    fn recv_frame(&mut self) -> Result<String> {
+        loop {
+            let message: Message = self.recv_message()?;
+            let payload = if message.opcode == Type::Text {
+                message.payload
+            } else if message.opcode == Type::Binary {
+                let mut payload = Vec::new();
+                ZlibDecoder::new(&message.payload[..]).read_to_end(&mut payload)?;
+                Cow::Owned(payload)
+            } else if message.opcode == Type::Close {
+                return Err(Error::Closed(message.cd_status_code,
+                    String::from_utf8_lossy(&message.payload).into_owned()));
+            } else {
+                continue;
+            };
+            return Ok(String::from_utf8_lossy(&payload).into_owned());
+        }
+    }
+}
+
+/// Heartbeats carry the last sequence number, which is null before the first dispatch.
+fn heartbeat(seq: Option<u64>) -> Value {
+    json!({
+        "op": 1,
+        "d": seq,
+    })
+}
+
+struct Recorder {
+    out: Box<Write + Send>,
+}
+
+impl Recorder {
+    /// Appends a frame as a single line. Line breaks can only be whitespace between
+    /// json tokens, so replacing them doesn't change the frame.
+    fn write(&mut self, frame: &str) {
+        // a broken recording must not stop the bot
+        if let Err(err) = writeln!(self.out, "{}", frame.replace('\n', " ")) {
+            writeln!(io::stderr(), "error: failed to record event: {}", err).expect("failed writing to stderr");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use serde_json::{self, Value};
+    use discord::{Error, Result};
+    use discord::model::Event;
+
+    use errors;
+    use gateway::Gateway;
+    use super::{Connector, FrameSender, FrameReceiver, RecordingGateway, Recorder, Delays, RESUME_ATTEMPTS};
+
+    const HELLO: &'static str = r#"{"op": 10, "d": {"heartbeat_interval": 3600000}}"#;
+    /// Discord asking for a heartbeat right away.
+    const HEARTBEAT: &'static str = r#"{"op": 1, "s": 1, "d": 1}"#;
+    const INVALIDATE: &'static str = r#"{"op": 9, "d": false}"#;
+    /// Marks where a connection was closed among the sent frames.
+    const CLOSED: &'static str = "<closed>";
+
+    /// Returns the `READY` and `GUILD_CREATE` frames of the fixtures.
+    fn setup() -> (String, String) {
+        let mut lines = include_str!("../../fixtures/setup.jsonl").lines().map(|s| s.to_string());
+        (lines.next().unwrap(), lines.next().unwrap())
+    }
+
+    /// Replaces discord with a script of connections.
+    struct FakeConnector {
+        /// Frames received on each connection which will be opened, `None` fails to open.
+        /// A connection drops once its frames are used up.
+        connections: Mutex<VecDeque<Option<Vec<String>>>>,
+        /// Frames sent on each opened connection.
+        sent: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl Connector for FakeConnector {
+        fn open(&self) -> Result<(Box<FrameSender>, Box<FrameReceiver>)> {
+            let frames = match self.connections.lock().unwrap().pop_front() {
+                Some(Some(frames)) => frames,
+                Some(None) => return Err(Error::Other("connection refused")),
+                None => return Err(Error::Other("script is exhausted")),
+            };
+            let mut sent = self.sent.lock().unwrap();
+            sent.push(Vec::new());
+            let sender = FakeSender { sent: self.sent.clone(), index: sent.len() - 1 };
+            Ok((Box::new(sender), Box::new(FakeReceiver { frames: frames.into_iter().collect() })))
+        }
+    }
+
+    struct FakeSender {
+        sent: Arc<Mutex<Vec<Vec<String>>>>,
+        index: usize,
+    }
+
+    impl FrameSender for FakeSender {
+        fn send_frame(&mut self, frame: &str) -> Result<()> {
+            self.sent.lock().unwrap()[self.index].push(frame.to_string());
+            Ok(())
+        }
+
+        fn close(&mut self) {
+            self.sent.lock().unwrap()[self.index].push(CLOSED.to_string());
+        }
+    }
+
+    struct FakeReceiver {
+        frames: VecDeque<String>,
+    }
+
+    impl FrameReceiver for FakeReceiver {
+        fn recv_frame(&mut self) -> Result<String> {
+            self.frames.pop_front().ok_or(Error::Other("connection dropped"))
+        }
+    }
+
+    #[derive(Clone)]
+    struct Recording(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Recording {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A session on the given connections, returning the frames sent on each connection
+    /// and everything recorded.
+    fn connect(connections: Vec<Option<Vec<String>>>)
+            -> Result<(RecordingGateway, Arc<Mutex<Vec<Vec<String>>>>, Recording)> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let connector = FakeConnector {
+            connections: Mutex::new(connections.into_iter().collect()),
+            sent: sent.clone(),
+        };
+        let recording = Recording(Arc::new(Mutex::new(Vec::new())));
+        let recorder = Arc::new(Mutex::new(Recorder { out: Box::new(recording.clone()) }));
+        let delays = Delays { resume: Duration::from_millis(0), identify: Duration::from_millis(0) };
+        let (con, _) = RecordingGateway::connect(Box::new(connector), "token", recorder, delays)?;
+        Ok((con, sent, recording))
+    }
+
+    fn parse(frame: &str) -> errors::Result<Value> {
+        Ok(unwrap!(serde_json::from_str(frame).ok(), "sent frame is no json: {}", frame))
+    }
+
+    fn is_server_create(evt: Option<Event>) -> bool {
+        match evt {
+            Some(Event::ServerCreate(..)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn identify_and_record_dispatches() -> errors::Result<()> {
+        let (ready, guild) = setup();
+        let (mut con, sent, recording) = connect(vec![Some(vec![HELLO.to_string(), ready.clone(), guild.clone()])])?;
+        {
+            let sent = sent.lock().unwrap();
+            assert_eq!(sent.len(), 1);
+            assert_eq!(sent[0].len(), 1);
+            let identify = parse(&sent[0][0])?;
+            assert_eq!(identify["op"].as_u64(), Some(2));
+            assert_eq!(identify["d"]["token"].as_str(), Some("token"));
+        }
+        assert!(is_server_create(con.recv_event()?));
+        let recorded = String::from_utf8_lossy(&recording.0.lock().unwrap()).into_owned();
+        assert_eq!(recorded, format!("{}\n{}\n", ready, guild));
+        Ok(())
+    }
+
+    #[test]
+    fn heartbeat_before_first_dispatch_is_null() -> errors::Result<()> {
+        let (ready, _) = setup();
+        let (_, sent, _) = connect(vec![Some(vec![HEARTBEAT.to_string(), ready])])?;
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0].len(), 2);
+        let heartbeat = parse(&sent[0][1])?;
+        assert_eq!(heartbeat["op"].as_u64(), Some(1));
+        assert!(heartbeat["d"].is_null());
+        Ok(())
+    }
+
+    #[test]
+    fn resume_after_connection_drop() -> errors::Result<()> {
+        let (ready, guild) = setup();
+        let (mut con, sent, _) = connect(vec![Some(vec![ready]), Some(vec![HEARTBEAT.to_string(), guild])])?;
+        assert!(is_server_create(con.recv_event()?));
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        let resume = parse(&sent[1][0])?;
+        assert_eq!(resume["op"].as_u64(), Some(6));
+        assert_eq!(resume["d"]["session_id"].as_str(), Some("fixture"));
+        assert_eq!(resume["d"]["seq"].as_u64(), Some(1));
+        // the resumed session keeps its sequence number
+        let heartbeat = parse(&sent[1][1])?;
+        assert_eq!(heartbeat["d"].as_u64(), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn resume_is_retried() -> errors::Result<()> {
+        let (ready, guild) = setup();
+        let (mut con, sent, _) = connect(vec![Some(vec![ready]), None, Some(Vec::new()), Some(vec![guild])])?;
+        assert!(is_server_create(con.recv_event()?));
+        let sent = sent.lock().unwrap();
+        // the refused connection never was opened
+        assert_eq!(sent.len(), 3);
+        for frames in &sent[1..] {
+            assert_eq!(parse(&frames[0])?["op"].as_u64(), Some(6));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn give_up_resuming() -> errors::Result<()> {
+        let (ready, _) = setup();
+        let mut connections = vec![Some(vec![ready])];
+        connections.extend((0..RESUME_ATTEMPTS).map(|_| None));
+        let (mut con, _, _) = connect(connections)?;
+        match con.recv_event() {
+            Err(Error::Other("connection refused")) => {},
+            other => assert!(false, "expected the last error after {} attempts, got {:?}", RESUME_ATTEMPTS, other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn identify_again_after_invalid_session() -> errors::Result<()> {
+        let (ready, guild) = setup();
+        let (mut con, sent, _) = connect(vec![Some(vec![ready.clone(), INVALIDATE.to_string(), ready, guild])])?;
+        match con.recv_event()? {
+            Some(Event::Ready(..)) => {},
+            other => assert!(false, "expected a new session, got {:?}", other),
+        }
+        {
+            let sent = sent.lock().unwrap();
+            assert_eq!(sent[0].len(), 2);
+            assert_eq!(parse(&sent[0][1])?["op"].as_u64(), Some(2));
+        }
+        assert!(is_server_create(con.recv_event()?));
+        Ok(())
+    }
+
+    #[test]
+    fn closed_session_is_not_resumed() -> errors::Result<()> {
+        let (ready, guild) = setup();
+        let (mut con, sent, _) = connect(vec![Some(vec![ready]), Some(vec![guild])])?;
+        let close = unwrap!(con.closer(), "the session can't be closed");
+        close();
+        match con.recv_event() {
+            Err(Error::Other("connection dropped")) => {},
+            other => assert!(false, "expected the connection to stay closed, got {:?}", other),
+        }
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].last().map(|s| s.as_str()), Some(CLOSED));
+        Ok(())
+    }
+}
@@ -1,44 +1,219 @@
 // `error_chain!` can recurse deeply
 #![recursion_limit = "1024"]extern crate toml;
 
-extern crate rustc_serialize;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_ignored;
+extern crate atty;
+#[macro_use]
+extern crate clap;
 extern crate discord;
 extern crate strfmt;
+#[macro_use]
 extern crate serde_json;
+extern crate discord_json;
+extern crate websocket;
+extern crate flate2;
 #[macro_use]
 extern crate error_chain;
+#[cfg(test)]
+extern crate rand;
 
 #[macro_use]
 mod errors;
 mod expiring_map;
+mod textdiff;
 mod modelext;
 mod config;
+mod gateway;
+mod restart;
 mod bot;
+mod replay;
 
 use std::time::{Instant, Duration};
+use std::thread;
+use std::process;
+use std::io::{self, Write};
+use std::path::Path;
+
+use clap::{App, Arg, SubCommand};
 
-use config::Config;
+use config::{Config, ConfigWatcher};
+use restart::RestartPolicy;
 use bot::Bot;
 
 fn main() {
+    let matches = App::new("dilobo")
+        .version(crate_version!())
+        .about("Discord bot logging the events of servers into a channel")
+        .arg(Arg::with_name("config")
+            .long("config")
+            .short("c")
+            .value_name("PATH")
+            .default_value("Config.toml")
+            .help("Config file to use"))
+        .arg(Arg::with_name("token")
+            .long("token")
+            .value_name("TOKEN")
+            .help("Bot token, overrides every other way to give it"))
+        .arg(Arg::with_name("token-file")
+            .long("token-file")
+            .value_name("PATH")
+            .help("File containing the bot token"))
+        .arg(Arg::with_name("record")
+            .long("record")
+            .value_name("FILE")
+            .help("Appends all received gateway events to the file while running"))
+        .arg(Arg::with_name("replay")
+            .long("replay")
+            .value_name("FILE")
+            .conflicts_with("record")
+            .help("Runs the bot offline on events recorded with --record and prints its log output"))
+        .subcommand(SubCommand::with_name("run")
+            .about("Runs the bot, which is the default"))
+        .subcommand(SubCommand::with_name("check")
+            .about("Validates the config and prints every template rendered with sample values"))
+        .subcommand(SubCommand::with_name("print-default-config")
+            .about("Prints a commented config with all built-in defaults"))
+        .get_matches();
+
+    let path = matches.value_of("config").unwrap();
+    match matches.subcommand() {
+        ("print-default-config", _) => {
+            print!("{}", config::default_config());
+            return;
+        },
+        ("check", _) => check(path),
+        _ => {}
+    }
+
     println!("Reading config...");
-    let mut config = Config::load("Config.toml");
+    let replay = matches.value_of("replay");
+    let token = matches.value_of("token").map(|s| s.to_string());
+    let token_file = matches.value_of("token-file").map(|s| s.to_string());
+    let res = Config::load(path).and_then(|mut config| {
+        // a replay never connects to discord, so it doesn't need a token
+        if replay.is_none() {
+            config.resolve_token(token, token_file)?;
+        }
+        config.check()?;
+        Ok(config)
+    });
+    let config = match res {
+        Ok(config) => config,
+        Err(err) => {
+            print_error(&err);
+            process::exit(1);
+        }
+    };
     println!("Config read successfully");
-    config.validate().unwrap();
 
-    let mintime = Duration::from_secs(5);
-    let mut fastexits = 0;
+    match replay {
+        Some(file) => replay_file(config, file),
+        None => run(path, config, matches.value_of("record").map(Path::new)),
+    }
+}
+
+/// Runs the bot, restarting it after errors, until it is stopped or gives up.
+fn run(path: &str, config: Config, record: Option<&Path>) {
+    let poll_interval = config.bot.as_ref().and_then(|b| b.config_poll_interval_ms).unwrap_or(1000);
+    let watcher = if poll_interval > 0 {
+        Some(ConfigWatcher::new(path, config.clone(), Duration::from_millis(poll_interval)))
+    } else {
+        None
+    };
+
+    let mut policy = RestartPolicy::new(config.restart.as_ref());
+    let mut last_error = None;
     loop {
         let time = Instant::now();
-        let mut bot = Bot::new(config.clone()).unwrap();
-        bot.run();
-        if time + mintime < Instant::now() {
-            fastexits += 1;
-        } else {
-            fastexits = 0;
+        // restarts use the most recently reloaded config
+        let (generation, config) = match watcher {
+            Some(ref watcher) => watcher.current(),
+            None => (0, config.clone()),
+        };
+        let err = match Bot::new(config, record) {
+            Ok(mut bot) => {
+                if let Some(ref watcher) = watcher {
+                    bot.watch_config(watcher.clone(), generation);
+                }
+                if let Some(ref err) = last_error {
+                    bot.report_restart(policy.failures(), err);
+                }
+                match bot.run() {
+                    Ok(()) => return,
+                    Err(err) => err,
+                }
+            },
+            Err(err) => err,
+        };
+        writeln!(io::stderr(), "Bot stopped: {}", err).expect("failed writing to stderr");
+        match policy.failed(time.elapsed()) {
+            Ok(delay) => {
+                println!("Restarting in {}.{:03}s (attempt {})", delay.as_secs(),
+                    delay.subsec_nanos() / 1_000_000, policy.failures());
+                thread::sleep(delay);
+            },
+            Err(err) => {
+                print_error(&err);
+                process::exit(1);
+            }
         }
-        if fastexits > 3 {
-            panic!("FUCK THIS SHIT I'M OUT");
+        last_error = Some(err.to_string());
+    }
+}
+
+/// Validates the config, prints every template rendered with sample values and exits.
+fn check(path: &str) -> ! {
+    let res = Config::load(path).and_then(|config| {
+        config.check()?;
+        Ok(config)
+    });
+    let config = match res {
+        Ok(config) => config,
+        Err(err) => {
+            print_error(&err);
+            process::exit(1);
+        }
+    };
+    for server in config.server.iter() {
+        match (server.server_id, server.server_name.as_ref()) {
+            (Some(id), _) => println!("[server {}]", id),
+            (None, Some(name)) => println!("[server {:?}]", name),
+            (None, None) => unreachable!(),
+        }
+        for (field, sample) in server.render_samples() {
+            match sample {
+                Some(sample) => println!("{}: {}", field, sample),
+                None => println!("{}: (disabled)", field),
+            }
+        }
+        println!();
+    }
+    println!("{} is valid", path);
+    process::exit(0);
+}
+
+/// Runs the bot offline on events recorded with `--record`, prints its log output and exits.
+fn replay_file(config: Config, path: &str) -> ! {
+    let res = replay::load_events(path).and_then(|events| replay::run_events(config, events));
+    match res {
+        Ok(sent) => {
+            println!("Log output:");
+            print!("{}", replay::render(&sent));
+            process::exit(0);
+        },
+        Err(err) => {
+            print_error(&err);
+            process::exit(1);
         }
     }
 }
+
+fn print_error(err: &errors::Error) {
+    writeln!(io::stderr(), "error: {}", err).expect("failed writing to stderr");
+    for e in err.iter().skip(1) {
+        writeln!(io::stderr(), "caused by: {}", e).expect("failed writing to stderr");
+    }
+}
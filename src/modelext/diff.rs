@@ -14,14 +14,18 @@ use discord::model::{
     EmojiId,
     Emoji,
     PublicChannel,
+    PermissionOverwrite,
     PermissionOverwriteType,
     Permissions,
 };
 use discord::model::permissions::{
     CREATE_INVITE,
+    KICK_MEMBERS,
+    BAN_MEMBERS,
+    ADMINISTRATOR,
     MANAGE_CHANNELS,
-    MANAGE_ROLES,
-    MANAGE_WEBHOOKS,
+    MANAGE_SERVER,
+    ADD_REACTIONS,
     READ_MESSAGES,
     SEND_MESSAGES,
     SEND_TTS_MESSAGES,
@@ -31,9 +35,19 @@ use discord::model::permissions::{
     READ_HISTORY,
     MENTION_EVERYONE,
     EXTERNAL_EMOJIS,
-    ADD_REACTIONS,
+    VOICE_CONNECT,
+    VOICE_SPEAK,
+    VOICE_MUTE_MEMBERS,
+    VOICE_DEAFEN_MEMBERS,
+    VOICE_MOVE_MEMBERS,
+    VOICE_USE_VAD,
+    CHANGE_NICKNAMES,
+    MANAGE_NICKNAMES,
+    MANAGE_ROLES,
+    MANAGE_WEBHOOKS,
+    MANAGE_EMOJIS,
 };
-use serde_json::Value;
+use discord_json::Value;
 
 use errors::*;
 
@@ -285,9 +299,12 @@ pub enum PermissionType {
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum ChannelPermission {
     CreateInvite,
+    KickMembers,
+    BanMembers,
+    Administrator,
     ManageChannel,
-    ManagePermissions,
-    ManageWebhooks,
+    ManageServer,
+    AddReactions,
     ReadMessages,
     SendMessages,
     SendTtsMessages,
@@ -297,7 +314,59 @@ pub enum ChannelPermission {
     ReadMessageHistory,
     MentionEveryone,
     UseExternealEmojis,
-    AddReactions,
+    VoiceConnect,
+    VoiceSpeak,
+    VoiceMuteMembers,
+    VoiceDeafenMembers,
+    VoiceMoveMembers,
+    VoiceUseVad,
+    ChangeNicknames,
+    ManageNicknames,
+    ManagePermissions,
+    ManageWebhooks,
+    ManageEmojis,
+}
+
+/// Every known permission with its bit and the name used in templates, in the order of the bits.
+const PERMISSIONS: &'static [(ChannelPermission, Permissions, &'static str)] = &[
+    (ChannelPermission::CreateInvite, CREATE_INVITE, "create_invite"),
+    (ChannelPermission::KickMembers, KICK_MEMBERS, "kick_members"),
+    (ChannelPermission::BanMembers, BAN_MEMBERS, "ban_members"),
+    (ChannelPermission::Administrator, ADMINISTRATOR, "administrator"),
+    (ChannelPermission::ManageChannel, MANAGE_CHANNELS, "manage_channels"),
+    (ChannelPermission::ManageServer, MANAGE_SERVER, "manage_server"),
+    (ChannelPermission::AddReactions, ADD_REACTIONS, "add_reactions"),
+    (ChannelPermission::ReadMessages, READ_MESSAGES, "read_messages"),
+    (ChannelPermission::SendMessages, SEND_MESSAGES, "send_messages"),
+    (ChannelPermission::SendTtsMessages, SEND_TTS_MESSAGES, "send_tts_messages"),
+    (ChannelPermission::ManageMessages, MANAGE_MESSAGES, "manage_messages"),
+    (ChannelPermission::EmbedLinks, EMBED_LINKS, "embed_links"),
+    (ChannelPermission::AttachFiles, ATTACH_FILES, "attach_files"),
+    (ChannelPermission::ReadMessageHistory, READ_HISTORY, "read_history"),
+    (ChannelPermission::MentionEveryone, MENTION_EVERYONE, "mention_everyone"),
+    (ChannelPermission::UseExternealEmojis, EXTERNAL_EMOJIS, "external_emojis"),
+    (ChannelPermission::VoiceConnect, VOICE_CONNECT, "voice_connect"),
+    (ChannelPermission::VoiceSpeak, VOICE_SPEAK, "voice_speak"),
+    (ChannelPermission::VoiceMuteMembers, VOICE_MUTE_MEMBERS, "voice_mute_members"),
+    (ChannelPermission::VoiceDeafenMembers, VOICE_DEAFEN_MEMBERS, "voice_deafen_members"),
+    (ChannelPermission::VoiceMoveMembers, VOICE_MOVE_MEMBERS, "voice_move_members"),
+    (ChannelPermission::VoiceUseVad, VOICE_USE_VAD, "voice_use_vad"),
+    (ChannelPermission::ChangeNicknames, CHANGE_NICKNAMES, "change_nicknames"),
+    (ChannelPermission::ManageNicknames, MANAGE_NICKNAMES, "manage_nicknames"),
+    (ChannelPermission::ManagePermissions, MANAGE_ROLES, "manage_roles"),
+    (ChannelPermission::ManageWebhooks, MANAGE_WEBHOOKS, "manage_webhooks"),
+    (ChannelPermission::ManageEmojis, MANAGE_EMOJIS, "manage_emojis"),
+];
+
+impl ChannelPermission {
+    pub fn permissions(&self) -> Permissions {
+        PERMISSIONS.iter().find(|&&(p, _, _)| p == *self).unwrap().1
+    }
+
+    /// Name of the permission as used in templates, e.g. `send_messages`.
+    pub fn name(&self) -> String {
+        PERMISSIONS.iter().find(|&&(p, _, _)| p == *self).unwrap().2.to_string()
+    }
 }
 
 pub struct MyVec<T>(Vec<T>);
@@ -318,29 +387,30 @@ impl<T> DerefMut for MyVec<T> {
     }
 }
 
+// Bits without a name can't be reported: discord-rs parses permissions with
+// `from_bits_truncate`, so they are already dropped when an event is decoded.
 impl From<Permissions> for MyVec<ChannelPermission> {
     fn from(perm: Permissions) -> MyVec<ChannelPermission> {
-        let mut res = Vec::new();
-        if perm.contains(CREATE_INVITE) { res.push(ChannelPermission::CreateInvite); }
-        if perm.contains(MANAGE_CHANNELS) { res.push(ChannelPermission::ManageChannel); }
-        if perm.contains(MANAGE_ROLES) { res.push(ChannelPermission::ManagePermissions); }
-        if perm.contains(MANAGE_WEBHOOKS) { res.push(ChannelPermission::ManageWebhooks); }
-        if perm.contains(READ_MESSAGES) { res.push(ChannelPermission::ReadMessages); }
-        if perm.contains(SEND_MESSAGES) { res.push(ChannelPermission::SendMessages); }
-        if perm.contains(SEND_TTS_MESSAGES) { res.push(ChannelPermission::SendTtsMessages); }
-        if perm.contains(MANAGE_MESSAGES) { res.push(ChannelPermission::ManageMessages); }
-        if perm.contains(EMBED_LINKS) { res.push(ChannelPermission::EmbedLinks); }
-        if perm.contains(ATTACH_FILES) { res.push(ChannelPermission::AttachFiles); }
-        if perm.contains(READ_HISTORY) { res.push(ChannelPermission::ReadMessageHistory); }
-        if perm.contains(MENTION_EVERYONE) { res.push(ChannelPermission::MentionEveryone); }
-        if perm.contains(EXTERNAL_EMOJIS) { res.push(ChannelPermission::UseExternealEmojis); }
-        if perm.contains(ADD_REACTIONS) { res.push(ChannelPermission::AddReactions); }
-        res.into()
+        PERMISSIONS.iter()
+            .filter(|&&(_, bits, _)| perm.contains(bits))
+            .map(|&(channel_perm, _, _)| channel_perm)
+            .collect::<Vec<_>>()
+            .into()
     }
 }
 
+/// Changes of a channel.
+///
+/// Permission changes are reported per permission as transition between the states of the
+/// overwrite, where None means that there is no overwrite for the user or role.
+/// A created overwrite is reported before the permissions it sets, a removed one after
+/// the permissions it set, so an overwrite without any permission is reported as well.
 pub enum ChannelUpdateDiff {
     Name(String, String),
+    UserOverwriteAdded(UserId),
+    UserOverwriteRemoved(UserId),
+    RoleOverwriteAdded(RoleId),
+    RoleOverwriteRemoved(RoleId),
     UserPermission(UserId, ChannelPermission, Option<PermissionType>, Option<PermissionType>),
     RolePermission(RoleId, ChannelPermission, Option<PermissionType>, Option<PermissionType>),
     Topic(Option<String>, Option<String>),
@@ -349,6 +419,75 @@ pub enum ChannelUpdateDiff {
     UserLimit(Option<u64>, Option<u64>),
 }
 
+impl ChannelUpdateDiff {
+    pub fn apply(&self, channel: &mut PublicChannel) -> Result<()> {
+        match self {
+            &ChannelUpdateDiff::Name(_, ref new) => channel.name = new.clone(),
+            &ChannelUpdateDiff::UserOverwriteAdded(id) =>
+                add_overwrite(channel, PermissionOverwriteType::Member(id))?,
+            &ChannelUpdateDiff::UserOverwriteRemoved(id) =>
+                remove_overwrite(channel, PermissionOverwriteType::Member(id))?,
+            &ChannelUpdateDiff::RoleOverwriteAdded(id) =>
+                add_overwrite(channel, PermissionOverwriteType::Role(id))?,
+            &ChannelUpdateDiff::RoleOverwriteRemoved(id) =>
+                remove_overwrite(channel, PermissionOverwriteType::Role(id))?,
+            &ChannelUpdateDiff::UserPermission(id, perm, _, new) =>
+                apply_overwrite(channel, PermissionOverwriteType::Member(id), perm, new)?,
+            &ChannelUpdateDiff::RolePermission(id, perm, _, new) =>
+                apply_overwrite(channel, PermissionOverwriteType::Role(id), perm, new)?,
+            &ChannelUpdateDiff::Topic(_, ref new) => channel.topic = new.clone(),
+            &ChannelUpdateDiff::Position(_, new) => channel.position = new,
+            &ChannelUpdateDiff::Bitrate(_, new) => channel.bitrate = new,
+            &ChannelUpdateDiff::UserLimit(_, new) => channel.user_limit = new,
+        }
+        Ok(())
+    }
+}
+
+/// State of a permission in an overwrite, or None if there is no overwrite.
+fn overwrite_state(overwrite: Option<&PermissionOverwrite>, perm: ChannelPermission) -> Option<PermissionType> {
+    overwrite.map(|o| {
+        if o.allow.contains(perm.permissions()) {
+            PermissionType::Allow
+        } else if o.deny.contains(perm.permissions()) {
+            PermissionType::Deny
+        } else {
+            PermissionType::Default
+        }
+    })
+}
+
+fn add_overwrite(channel: &mut PublicChannel, kind: PermissionOverwriteType) -> Result<()> {
+    assert!(channel.permission_overwrites.iter().all(|o| o.kind != kind), "overwrite {:?} already exists", kind);
+    channel.permission_overwrites.push(PermissionOverwrite {
+        kind: kind,
+        allow: Permissions::empty(),
+        deny: Permissions::empty(),
+    });
+    Ok(())
+}
+
+fn remove_overwrite(channel: &mut PublicChannel, kind: PermissionOverwriteType) -> Result<()> {
+    let pos = unwrap!(channel.permission_overwrites.iter().position(|o| o.kind == kind));
+    channel.permission_overwrites.remove(pos);
+    Ok(())
+}
+
+/// Sets a single permission of an existing overwrite. None resets it like Default,
+/// the overwrite itself is removed by its own diff.
+fn apply_overwrite(channel: &mut PublicChannel, kind: PermissionOverwriteType, perm: ChannelPermission,
+        new: Option<PermissionType>) -> Result<()> {
+    let overwrite = unwrap!(channel.permission_overwrites.iter_mut().find(|o| o.kind == kind));
+    overwrite.allow.remove(perm.permissions());
+    overwrite.deny.remove(perm.permissions());
+    match new {
+        Some(PermissionType::Allow) => overwrite.allow.insert(perm.permissions()),
+        Some(PermissionType::Deny) => overwrite.deny.insert(perm.permissions()),
+        Some(PermissionType::Default) | None => {},
+    }
+    Ok(())
+}
+
 impl Diff for PublicChannel {
     type Other = PublicChannel;
     type Output = ChannelUpdateDiff;
@@ -370,116 +509,428 @@ impl Diff for PublicChannel {
         if self.user_limit != other.user_limit {
             res.push(ChannelUpdateDiff::UserLimit(self.user_limit, other.user_limit));
         }
-        if self.permission_overwrites != other.permission_overwrites {
-            let mut own: HashMap<_,_> = self.permission_overwrites.iter().map(|perm| (perm.kind, perm)).collect();
-            for other in other.permission_overwrites.iter() {
-                let own = own.remove(&other.kind);
-                if let None = own {
-                    let tmp: MyVec<_> = other.allow.into();
-                    for &allow in tmp.iter() {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, allow, None, Some(PermissionType::Allow))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, allow, None, Some(PermissionType::Allow))),
-                        }
-                    }
-                    let tmp: MyVec<_> = other.deny.into();
-                    for &deny in tmp.iter() {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, deny, None, Some(PermissionType::Deny))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, deny, None, Some(PermissionType::Deny))),
-                        }
-                    }
+        // overwrites of the new channel first, then the removed ones
+        let mut kinds: Vec<_> = other.permission_overwrites.iter().map(|o| o.kind).collect();
+        for overwrite in self.permission_overwrites.iter() {
+            if !kinds.contains(&overwrite.kind) {
+                kinds.push(overwrite.kind);
+            }
+        }
+        for kind in kinds {
+            let old = self.permission_overwrites.iter().find(|o| o.kind == kind);
+            let new = other.permission_overwrites.iter().find(|o| o.kind == kind);
+            // only permissions which are set in either overwrite can have changed
+            let touched = old.into_iter().chain(new)
+                .fold(Permissions::empty(), |acc, o| acc | o.allow | o.deny);
+            let perms: MyVec<ChannelPermission> = touched.into();
+            if old.is_none() {
+                res.push(match kind {
+                    PermissionOverwriteType::Member(id) => ChannelUpdateDiff::UserOverwriteAdded(id),
+                    PermissionOverwriteType::Role(id) => ChannelUpdateDiff::RoleOverwriteAdded(id),
+                });
+            }
+            for &perm in perms.iter() {
+                let from = overwrite_state(old, perm);
+                let to = overwrite_state(new, perm);
+                // a missing overwrite leaves the permission untouched just like Default,
+                // adding or removing the overwrite itself is reported on its own
+                if from.unwrap_or(PermissionType::Default) == to.unwrap_or(PermissionType::Default) {
                     continue;
                 }
-                let own = own.unwrap();
-                let tmp: MyVec<_> = own.allow.clone().into();
-                let own_allow: HashSet<_> = tmp.iter().collect();
-                let tmp: MyVec<_> = own.deny.clone().into();
-                let own_deny: HashSet<_> = tmp.iter().collect();
-                let tmp: MyVec<_> = other.allow.clone().into();
-                let other_allow: HashSet<_> = tmp.iter().collect();
-                let tmp: MyVec<_> = other.deny.clone().into();
-                let other_deny: HashSet<_> = tmp.iter().collect();
-                for &&removed in own_allow.difference(&other_allow) {
-                    if other_deny.contains(&removed) {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, removed, Some(PermissionType::Allow), Some(PermissionType::Deny))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, removed, Some(PermissionType::Allow), Some(PermissionType::Deny))),
-                        }
-                    } else {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, removed, Some(PermissionType::Allow), Some(PermissionType::Default))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, removed, Some(PermissionType::Allow), Some(PermissionType::Default))),
-                        }
-                    }
-                }
-                for &&added in own_deny.difference(&other_deny) {
-                    if other_allow.contains(&added) {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, added, Some(PermissionType::Deny), Some(PermissionType::Allow))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, added, Some(PermissionType::Deny), Some(PermissionType::Allow))),
-                        }
-                    } else {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, added, Some(PermissionType::Deny), Some(PermissionType::Default))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, added, Some(PermissionType::Deny), Some(PermissionType::Default))),
-                        }
-                    }
-                }
-                for &&allow in other_allow.difference(&own_allow) {
-                    if !own_deny.contains(&allow) {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, allow, Some(PermissionType::Default), Some(PermissionType::Allow))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, allow, Some(PermissionType::Default), Some(PermissionType::Allow))),
-                        }
-                    }
+                match kind {
+                    PermissionOverwriteType::Member(id) =>
+                        res.push(ChannelUpdateDiff::UserPermission(id, perm, from, to)),
+                    PermissionOverwriteType::Role(id) =>
+                        res.push(ChannelUpdateDiff::RolePermission(id, perm, from, to)),
                 }
-                for &&deny in other_deny.difference(&own_deny) {
-                    if !own_allow.contains(&deny) {
-                        match other.kind {
-                            PermissionOverwriteType::Member(id) =>
-                                res.push(ChannelUpdateDiff::UserPermission(id, deny, Some(PermissionType::Default), Some(PermissionType::Deny))),
-                            PermissionOverwriteType::Role(id) =>
-                                res.push(ChannelUpdateDiff::RolePermission(id, deny, Some(PermissionType::Default), Some(PermissionType::Deny))),
-                        }
-                    }
+            }
+            if new.is_none() {
+                res.push(match kind {
+                    PermissionOverwriteType::Member(id) => ChannelUpdateDiff::UserOverwriteRemoved(id),
+                    PermissionOverwriteType::Role(id) => ChannelUpdateDiff::RoleOverwriteRemoved(id),
+                });
+            }
+        }
+        Ok(res)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    use discord::model::{MessageId, ChannelId, ServerId, ChannelType};
+    use discord_json::Value;
+
+    use errors::*;
+    use super::*;
+
+    const ITERATIONS: usize = 1000;
+
+    /// Returns a generator seeded with a constant, so failures are reproducible.
+    fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([0x2545_f491, 0x4f6c_dd1d, 0x9e37_79b9, 0x7f4a_7c15])
+    }
+
+    fn pick<T: Clone>(rng: &mut XorShiftRng, values: &[T]) -> T {
+        rng.choose(values).unwrap().clone()
+    }
+
+    fn maybe<T: Clone>(rng: &mut XorShiftRng, values: &[T]) -> Option<T> {
+        if rng.gen() { Some(pick(rng, values)) } else { None }
+    }
+
+    /// Returns a random subset of the values, keeping their order.
+    fn subset<T: Clone>(rng: &mut XorShiftRng, values: &[T]) -> Vec<T> {
+        values.iter().filter(|_| rng.gen()).cloned().collect()
+    }
+
+    /// Runs `check` with the given number of generated inputs.
+    fn generated<F>(check: F) -> Result<()> where F: Fn(&mut XorShiftRng) -> Result<()> {
+        let mut rng = rng();
+        for i in 0..ITERATIONS {
+            check(&mut rng).chain_err(|| format!("failed in iteration {}", i))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn message_diff() -> Result<()> {
+        generated(check_message)
+    }
+
+    #[test]
+    fn member_diff() -> Result<()> {
+        generated(check_member)
+    }
+
+    #[test]
+    fn emojis_diff() -> Result<()> {
+        generated(check_emojis)
+    }
+
+    #[test]
+    fn channel_diff() -> Result<()> {
+        generated(check_channel)
+    }
+
+    fn user(id: u64) -> User {
+        User {
+            id: UserId(id),
+            name: format!("user{}", id),
+            discriminator: id as u16,
+            avatar: None,
+            bot: false,
+        }
+    }
+
+    fn attachment(id: u64) -> Attachment {
+        Attachment {
+            id: id.to_string(),
+            filename: format!("file{}.txt", id),
+            url: format!("https://example.com/file{}.txt", id),
+            proxy_url: format!("https://example.com/proxy/file{}.txt", id),
+            size: id * 100,
+            dimensions: None,
+        }
+    }
+
+    const TEXTS: &'static [&'static str] = &["", "a", "hello", "hello world", "hello big world"];
+
+    fn text(rng: &mut XorShiftRng) -> String {
+        pick(rng, TEXTS).to_string()
+    }
+
+    fn maybe_text(rng: &mut XorShiftRng) -> Option<String> {
+        maybe(rng, TEXTS).map(|s| s.to_string())
+    }
+
+    fn check_message(rng: &mut XorShiftRng) -> Result<()> {
+        let users: Vec<_> = (1..5).map(user).collect();
+        let roles: Vec<_> = (1..5).map(RoleId).collect();
+        let attachments: Vec<_> = (1..4).map(attachment).collect();
+        let embeds: Vec<_> = TEXTS.iter().map(|s| Value::String(s.to_string())).collect();
+        let kinds = [MessageType::Regular, MessageType::MessagePinned];
+
+        let old = Message {
+            id: MessageId(1),
+            channel_id: ChannelId(1),
+            content: text(rng),
+            nonce: maybe_text(rng),
+            tts: rng.gen(),
+            timestamp: "2017-01-01T00:00:00+00:00".to_string(),
+            edited_timestamp: maybe_text(rng),
+            pinned: rng.gen(),
+            kind: pick(rng, &kinds),
+            author: user(1),
+            mention_everyone: rng.gen(),
+            mentions: subset(rng, &users),
+            mention_roles: subset(rng, &roles),
+            reactions: Vec::new(),
+            attachments: subset(rng, &attachments),
+            embeds: subset(rng, &embeds),
+        };
+        // the diff doesn't look at the timestamp and author, they can't change
+        let update = MessageUpdate {
+            id: old.id,
+            channel_id: old.channel_id,
+            kind: maybe(rng, &kinds),
+            content: maybe_text(rng),
+            nonce: maybe_text(rng),
+            tts: maybe(rng, &[true, false]),
+            pinned: maybe(rng, &[true, false]),
+            timestamp: None,
+            edited_timestamp: maybe_text(rng),
+            author: None,
+            mention_everyone: maybe(rng, &[true, false]),
+            mentions: if rng.gen() { Some(subset(rng, &users)) } else { None },
+            mention_roles: if rng.gen() { Some(subset(rng, &roles)) } else { None },
+            attachments: if rng.gen() { Some(subset(rng, &attachments)) } else { None },
+            embeds: if rng.gen() { Some(subset(rng, &embeds)) } else { None },
+        };
+
+        let mut expected = old.clone();
+        if let Some(kind) = update.kind { expected.kind = kind; }
+        if let Some(ref content) = update.content { expected.content = content.clone(); }
+        if let Some(ref nonce) = update.nonce { expected.nonce = Some(nonce.clone()); }
+        if let Some(tts) = update.tts { expected.tts = tts; }
+        if let Some(pinned) = update.pinned { expected.pinned = pinned; }
+        if let Some(ref time) = update.edited_timestamp { expected.edited_timestamp = Some(time.clone()); }
+        if let Some(mention_everyone) = update.mention_everyone { expected.mention_everyone = mention_everyone; }
+        if let Some(ref mentions) = update.mentions { expected.mentions = mentions.clone(); }
+        if let Some(ref roles) = update.mention_roles { expected.mention_roles = roles.clone(); }
+        if let Some(ref attachments) = update.attachments { expected.attachments = attachments.clone(); }
+        if let Some(ref embeds) = update.embeds { expected.embeds = embeds.clone(); }
+
+        let mut applied = old.clone();
+        for diff in old.diff(&update)? {
+            diff.apply(&mut applied)?;
+        }
+        assert_eq!(normalize_message(applied), normalize_message(expected), "old: {:?}, update: {:?}", old, update);
+        Ok(())
+    }
+
+    /// Renders a message with its lists sorted, as applying diffs doesn't keep their order.
+    fn normalize_message(mut msg: Message) -> String {
+        msg.mentions.sort_by_key(|u| u.id);
+        msg.mention_roles.sort();
+        msg.attachments.sort_by(|a, b| a.id.cmp(&b.id));
+        msg.embeds.sort_by_key(|e| e.to_string());
+        format!("{:?}", msg)
+    }
+
+    fn check_member(rng: &mut XorShiftRng) -> Result<()> {
+        let roles: Vec<_> = (1..5).map(RoleId).collect();
+        let old = Member {
+            user: user(1),
+            roles: subset(rng, &roles),
+            nick: maybe_text(rng),
+            joined_at: "2017-01-01T00:00:00+00:00".to_string(),
+            mute: false,
+            deaf: false,
+        };
+        let update = ServerMemberUpdate {
+            server_id: ServerId(1),
+            roles: subset(rng, &roles),
+            user: old.user.clone(),
+            nick: maybe_text(rng),
+        };
+
+        let mut expected = old.clone();
+        expected.roles = update.roles.clone();
+        expected.nick = update.nick.clone();
+
+        let mut applied = old.clone();
+        for diff in old.diff(&update)? {
+            diff.apply(&mut applied)?;
+        }
+        applied.roles.sort();
+        expected.roles.sort();
+        assert_eq!(format!("{:?}", applied), format!("{:?}", expected), "old: {:?}, update: {:?}", old, update);
+        Ok(())
+    }
+
+    fn check_emojis(rng: &mut XorShiftRng) -> Result<()> {
+        let old: HashMap<_, _> = emojis(rng).into_iter().map(|e| (e.id, e)).collect();
+        let update = emojis(rng);
+
+        let mut applied = old.clone();
+        for diff in old.diff(&update)? {
+            diff.apply(&mut applied)?;
+        }
+        let mut applied: Vec<_> = applied.into_iter().map(|(_, e)| e).collect();
+        applied.sort_by_key(|e| e.id);
+        assert_eq!(format!("{:?}", applied), format!("{:?}", update), "old: {:?}", old);
+        Ok(())
+    }
+
+    fn emojis(rng: &mut XorShiftRng) -> Vec<Emoji> {
+        let mut emojis = Vec::new();
+        for id in 1..5 {
+            if rng.gen() {
+                // the diff only tracks names so far, so nothing else is changed
+                emojis.push(Emoji {
+                    id: EmojiId(id),
+                    name: text(rng),
+                    managed: false,
+                    require_colons: true,
+                    roles: Vec::new(),
+                });
+            }
+        }
+        emojis
+    }
+
+    fn check_channel(rng: &mut XorShiftRng) -> Result<()> {
+        let old = channel(rng);
+        let new = channel(rng);
+        let diffs = old.diff(&new)?;
+        check_apply(&old, &new, diffs)
+    }
+
+    fn channel(rng: &mut XorShiftRng) -> PublicChannel {
+        let kinds = [
+            PermissionOverwriteType::Role(RoleId(1)),
+            PermissionOverwriteType::Role(RoleId(2)),
+            PermissionOverwriteType::Member(UserId(1)),
+            PermissionOverwriteType::Member(UserId(2)),
+        ];
+        let perms: MyVec<ChannelPermission> = Permissions::all().into();
+        let mut overwrites = Vec::new();
+        for &kind in subset(rng, &kinds).iter() {
+            let mut allow = Permissions::empty();
+            let mut deny = Permissions::empty();
+            for perm in perms.iter() {
+                match rng.gen_range(0, 3) {
+                    0 => allow.insert(perm.permissions()),
+                    1 => deny.insert(perm.permissions()),
+                    _ => {},
                 }
             }
-            for perm in own.values() {
-                let tmp: MyVec<_> = perm.allow.into();
-                for &allow in tmp.iter() {
-                    match perm.kind {
-                        PermissionOverwriteType::Member(id) =>
-                            res.push(ChannelUpdateDiff::UserPermission(id, allow, Some(PermissionType::Allow), None)),
-                        PermissionOverwriteType::Role(id) =>
-                            res.push(ChannelUpdateDiff::RolePermission(id, allow, Some(PermissionType::Allow), None)),
+            overwrites.push(PermissionOverwrite { kind: kind, allow: allow, deny: deny });
+        }
+        PublicChannel {
+            id: ChannelId(1),
+            name: text(rng),
+            server_id: ServerId(1),
+            kind: ChannelType::Text,
+            permission_overwrites: overwrites,
+            topic: maybe_text(rng),
+            position: rng.gen_range(0, 3),
+            last_message_id: None,
+            bitrate: maybe(rng, &[64000, 96000]),
+            user_limit: maybe(rng, &[0, 10]),
+            last_pin_timestamp: None,
+        }
+    }
+
+    /// Renders a channel with its overwrites sorted, as applying diffs doesn't keep their order.
+    fn normalize_channel(mut channel: PublicChannel) -> String {
+        channel.permission_overwrites.sort_by_key(|o| match o.kind {
+            PermissionOverwriteType::Role(id) => (0, id.0),
+            PermissionOverwriteType::Member(id) => (1, id.0),
+        });
+        format!("{:?}", channel)
+    }
+
+    /// Checks the diff of every transition of a single permission between no overwrite, Allow,
+    /// Default and Deny, and of all permissions flipping between Allow and Deny at once.
+    ///
+    /// Adding or removing the overwrite must be reported even if it sets nothing.
+    #[test]
+    fn overwrite_transitions() -> Result<()> {
+        let states = [
+            None,
+            Some(PermissionType::Allow),
+            Some(PermissionType::Default),
+            Some(PermissionType::Deny),
+        ];
+        let perms: MyVec<ChannelPermission> = Permissions::all().into();
+        for &perm in perms.iter() {
+            for &from in states.iter() {
+                for &to in states.iter() {
+                    let old = overwrite_channel(perm.permissions(), from);
+                    let new = overwrite_channel(perm.permissions(), to);
+                    let diffs = old.diff(&new)?;
+                    let mut expected = Vec::new();
+                    if from.is_none() && to.is_some() {
+                        expected.push(RoleChange::Added);
                     }
-                }
-                let tmp: MyVec<_> = perm.deny.into();
-                for &deny in tmp.iter() {
-                    match perm.kind {
-                        PermissionOverwriteType::Member(id) =>
-                            res.push(ChannelUpdateDiff::UserPermission(id, deny, Some(PermissionType::Deny), None)),
-                        PermissionOverwriteType::Role(id) =>
-                            res.push(ChannelUpdateDiff::RolePermission(id, deny, Some(PermissionType::Deny), None)),
+                    if from.unwrap_or(PermissionType::Default) != to.unwrap_or(PermissionType::Default) {
+                        expected.push(RoleChange::Permission(perm, from, to));
+                    }
+                    if from.is_some() && to.is_none() {
+                        expected.push(RoleChange::Removed);
                     }
+                    assert_eq!(role_changes(&diffs)?, expected, "transition of {:?} from {:?} to {:?}", perm, from, to);
+                    check_apply(&old, &new, diffs)?;
                 }
             }
         }
+
+        let all = Permissions::all();
+        for &(from, to) in [(PermissionType::Allow, PermissionType::Deny), (PermissionType::Deny, PermissionType::Allow)].iter() {
+            let old = overwrite_channel(all, Some(from));
+            let new = overwrite_channel(all, Some(to));
+            let diffs = old.diff(&new)?;
+            let expected: Vec<_> = perms.iter().map(|&perm| RoleChange::Permission(perm, Some(from), Some(to))).collect();
+            assert_eq!(role_changes(&diffs)?, expected);
+            check_apply(&old, &new, diffs)?;
+        }
+        Ok(())
+    }
+
+    /// Returns a channel with an overwrite for a single role, which sets `perms` to `state`.
+    fn overwrite_channel(perms: Permissions, state: Option<PermissionType>) -> PublicChannel {
+        let overwrite = state.map(|state| PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(RoleId(1)),
+            allow: if state == PermissionType::Allow { perms } else { Permissions::empty() },
+            deny: if state == PermissionType::Deny { perms } else { Permissions::empty() },
+        });
+        PublicChannel {
+            id: ChannelId(1),
+            name: "channel".to_string(),
+            server_id: ServerId(1),
+            kind: ChannelType::Text,
+            permission_overwrites: overwrite.into_iter().collect(),
+            topic: None,
+            position: 0,
+            last_message_id: None,
+            bitrate: None,
+            user_limit: None,
+            last_pin_timestamp: None,
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum RoleChange {
+        Added,
+        Permission(ChannelPermission, Option<PermissionType>, Option<PermissionType>),
+        Removed,
+    }
+
+    /// Extracts the overwrite changes of diffs, which must all be changes of role overwrites.
+    fn role_changes(diffs: &[ChannelUpdateDiff]) -> Result<Vec<RoleChange>> {
+        let mut res = Vec::new();
+        for diff in diffs {
+            match *diff {
+                ChannelUpdateDiff::RoleOverwriteAdded(_) => res.push(RoleChange::Added),
+                ChannelUpdateDiff::RolePermission(_, perm, from, to) => res.push(RoleChange::Permission(perm, from, to)),
+                ChannelUpdateDiff::RoleOverwriteRemoved(_) => res.push(RoleChange::Removed),
+                _ => assert!(false, "expected only changes of role overwrites"),
+            }
+        }
         Ok(res)
     }
+
+    fn check_apply(old: &PublicChannel, new: &PublicChannel, diffs: Vec<ChannelUpdateDiff>) -> Result<()> {
+        let mut applied = old.clone();
+        for diff in diffs {
+            diff.apply(&mut applied)?;
+        }
+        assert_eq!(normalize_channel(applied), normalize_channel(new.clone()), "old: {:?}, new: {:?}", old, new);
+        Ok(())
+    }
 }
@@ -17,6 +17,7 @@ use discord::model::{
 };
 use discord::model::permissions::{self, Permissions};
 use modelext::diff::{MessageUpdateDiff, ChannelUpdateDiff};
+use textdiff;
 
 use errors::*;
 
@@ -288,6 +289,7 @@ impl MergeIntoMap for MessageUpdateDiff {
                 map.insert("to".to_string(), format!("{:?}", to));
             },
             MessageUpdateDiff::Content(from, to) => {
+                map.insert("diff".to_string(), textdiff::inline(&from, &to));
                 map.insert("from".to_string(), from);
                 map.insert("to".to_string(), to);
             },
@@ -345,6 +347,12 @@ impl MergeIntoMap for ChannelUpdateDiff {
                 map.insert("from".to_string(), from);
                 map.insert("to".to_string(), to);
             },
+            ChannelUpdateDiff::UserOverwriteAdded(id) | ChannelUpdateDiff::UserOverwriteRemoved(id) => {
+                map.insert("user_id".to_string(), id.to_string());
+            },
+            ChannelUpdateDiff::RoleOverwriteAdded(id) | ChannelUpdateDiff::RoleOverwriteRemoved(id) => {
+                map.insert("role_id".to_string(), id.to_string());
+            },
             ChannelUpdateDiff::UserPermission(id, perm, from, to) => {
                 map.insert("user_id".to_string(), id.to_string());
                 map.insert("permission".to_string(), format!("{:?}", perm));
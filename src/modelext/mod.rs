@@ -2,4 +2,5 @@ mod merge_into_map;
 mod diff;
 
 pub use self::merge_into_map::MergeIntoMap;
-pub use self::diff::{Diff, MessageUpdateDiff, MemberUpdateDiff, EmojisUpdateDiff, ChannelUpdateDiff};
+pub use self::diff::{Diff, MessageUpdateDiff, MemberUpdateDiff, EmojisUpdateDiff, ChannelUpdateDiff,
+    ChannelPermission, PermissionType, MyVec};
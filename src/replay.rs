@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::fmt::Write as FmtWrite;
+
+use discord_json::{self, Value};
+use discord::model::{Event, GatewayEvent};
+
+use errors::*;
+use config::Config;
+use gateway::{FakeRest, Sent};
+use bot::Bot;
+
+/// Reads gateway events from a file with one raw gateway payload per line,
+/// as they are sent by discord (`{"op": 0, "s": .., "t": .., "d": ..}`).
+/// Empty lines are skipped, and so are events discord-rs can't decode, like the live bot does.
+pub fn load_events<P: AsRef<Path>>(path: P) -> Result<Vec<Event>> {
+    let path = path.as_ref();
+    let file = BufReader::new(File::open(path)?);
+    let mut events = Vec::new();
+    for (i, line) in file.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = discord_json::from_str(&line)
+            .chain_err(|| format!("invalid json in {}:{}", path.display(), i + 1))?;
+        let evt = match GatewayEvent::decode(value) {
+            Ok(GatewayEvent::Dispatch(_, evt)) => evt,
+            Err(err) => {
+                writeln!(io::stderr(), "error: skipping event in {}:{} which can't be decoded: {}",
+                    path.display(), i + 1, err).expect("failed writing to stderr");
+                continue;
+            },
+            Ok(other) => return Err(ErrorKind::AssertionFailed(format!("expected dispatch event in {}:{}, got {:?}",
+                path.display(), i + 1, other)).into()),
+        };
+        events.push(evt);
+    }
+    Ok(events)
+}
+
+/// Runs the bot offline on the given events and returns all messages it sent.
+/// The events must start with `Ready`.
+pub fn run_events(config: Config, events: Vec<Event>) -> Result<Vec<Sent>> {
+    let rest = Arc::new(FakeRest::new(events));
+    {
+        let mut bot = Bot::with_client(config, rest.clone())?;
+        bot.run()?;
+        // dropping the bot flushes its log queue
+    }
+    Ok(rest.sent())
+}
+
+/// Renders sent messages as text, prefixing every line with the channel it was sent to.
+///
+/// How lines are coalesced into messages depends on timing, so only the lines are rendered.
+pub fn render(sent: &[Sent]) -> String {
+    // channels are flushed independently, so only the order within a channel is fixed
+    let mut sent = sent.to_vec();
+    sent.sort_by_key(|msg| msg.channel.0);
+    let mut res = String::new();
+    for msg in sent {
+        for line in msg.text.lines() {
+            writeln!(res, "{}: {}", msg.channel, line).unwrap();
+        }
+        if let Some((filename, content)) = msg.file {
+            writeln!(res, "{}: [{}]", msg.channel, filename).unwrap();
+            for line in content.lines() {
+                writeln!(res, "{}| {}", msg.channel, line).unwrap();
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::path::Path;
+
+    use errors::*;
+    use config::Config;
+    use textdiff;
+    use super::*;
+
+    /// Runs the bot on a fixture and returns the rendered log output.
+    ///
+    /// The events of the fixture are `setup.jsonl` in the fixture root followed by `events.jsonl`
+    /// of the fixture. Its `Config.toml` is used if it exists, otherwise the one in the root.
+    /// The bot runs on a copy of the config, changes it makes to the copy are rendered as a diff.
+    fn run_fixture(root: &Path, dir: &Path) -> Result<String> {
+        let config_path = if dir.join("Config.toml").exists() {
+            dir.join("Config.toml")
+        } else {
+            root.join("Config.toml")
+        };
+        let mut text = String::new();
+        File::open(&config_path)?.read_to_string(&mut text)?;
+        let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+        let copy = env::temp_dir().join(format!("dilobo-fixture-{}.toml", name));
+        File::create(&copy)?.write_all(text.as_bytes())?;
+        let config = Config::load(&copy)?;
+        config.check()?;
+        let mut events = load_events(root.join("setup.jsonl"))?;
+        events.extend(load_events(dir.join("events.jsonl"))?);
+        let mut output = render(&run_events(config, events)?);
+
+        let mut edited = String::new();
+        File::open(&copy)?.read_to_string(&mut edited)?;
+        fs::remove_file(&copy)?;
+        if edited != text {
+            for line in textdiff::unified(&text, &edited).lines() {
+                output += &format!("config| {}\n", line);
+            }
+        }
+        Ok(output)
+    }
+
+    /// Runs every fixture in `root` and compares its output with its `expected.txt`.
+    ///
+    /// If `bless` is true, `expected.txt` is overwritten with the output instead.
+    /// Returns whether all fixtures passed.
+    fn check_fixtures(root: &Path, bless: bool) -> Result<bool> {
+        let mut dirs: Vec<_> = fs::read_dir(root)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        dirs.sort();
+
+        let mut passed = true;
+        for dir in dirs {
+            let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+            let output = run_fixture(root, &dir).chain_err(|| format!("fixture {} failed to run", name))?;
+            let expected_path = dir.join("expected.txt");
+            if bless {
+                File::create(&expected_path)?.write_all(output.as_bytes())?;
+                println!("fixture {} ... blessed", name);
+                continue;
+            }
+            let mut expected = String::new();
+            if expected_path.exists() {
+                File::open(&expected_path)?.read_to_string(&mut expected)?;
+            }
+            if output == expected {
+                println!("fixture {} ... ok", name);
+            } else {
+                passed = false;
+                println!("fixture {} ... FAILED", name);
+                print!("{}", textdiff::unified(&expected, &output));
+            }
+        }
+        Ok(passed)
+    }
+
+    /// Runs the fixtures in `fixtures/`.
+    ///
+    /// With `DILOBO_BLESS=1` the expected output of every fixture is replaced by its actual output.
+    #[test]
+    fn fixtures() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let bless = env::var("DILOBO_BLESS").map(|v| v == "1").unwrap_or(false);
+        match check_fixtures(&root, bless) {
+            // the crate's `assert!` returns an error instead of panicking
+            Ok(passed) => if !passed { panic!("fixture output differs from expected.txt") },
+            Err(err) => panic!("{}", err.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ")),
+        }
+    }
+}
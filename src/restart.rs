@@ -0,0 +1,157 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::cmp;
+
+use config::RestartConfig;
+use errors::*;
+
+/// Decides if and when the bot is restarted after it stopped with an error.
+///
+/// The delay between restarts grows exponentially with every consecutive failure, with a
+/// random jitter of up to half the delay on top. A run which lasted at least `reset_after`
+/// resets the number of consecutive failures.
+pub struct RestartPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_retries: u32,
+    reset_after: Duration,
+    failures: u32,
+    // Returns the random part of a delay, given its maximum
+    jitter: fn(Duration) -> Duration,
+}
+
+impl RestartPolicy {
+    pub fn new(config: Option<&RestartConfig>) -> RestartPolicy {
+        let initial_delay = config.and_then(|c| c.initial_delay_ms).unwrap_or(1000);
+        let max_delay = config.and_then(|c| c.max_delay_ms).unwrap_or(300000);
+        let max_retries = config.and_then(|c| c.max_retries).unwrap_or(10);
+        let reset_after = config.and_then(|c| c.reset_after_secs).unwrap_or(600);
+        RestartPolicy {
+            initial_delay: Duration::from_millis(initial_delay),
+            max_delay: Duration::from_millis(max_delay),
+            max_retries: max_retries,
+            reset_after: Duration::from_secs(reset_after),
+            failures: 0,
+            jitter: jitter,
+        }
+    }
+
+    /// Number of consecutive failures so far.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Registers that a run, which lasted for `uptime`, failed.
+    /// Returns how long to wait before restarting, or an error if we must give up.
+    pub fn failed(&mut self, uptime: Duration) -> Result<Duration> {
+        if uptime >= self.reset_after {
+            self.failures = 0;
+        }
+        self.failures += 1;
+        if self.failures > self.max_retries {
+            return Err(ErrorKind::RestartLimitReached(self.max_retries).into());
+        }
+        let factor = 1u32.checked_shl(self.failures - 1).unwrap_or(u32::max_value());
+        let delay = self.initial_delay.checked_mul(factor).unwrap_or(self.max_delay);
+        let delay = cmp::min(delay, self.max_delay);
+        Ok(delay + (self.jitter)(delay / 2))
+    }
+}
+
+/// Returns a pseudo-random duration between zero and `max`.
+fn jitter(max: Duration) -> Duration {
+    let max_millis = max.as_secs() * 1000 + (max.subsec_nanos() / 1_000_000) as u64;
+    if max_millis == 0 {
+        return Duration::from_secs(0);
+    }
+    // the sub-second part of the current time is random enough to spread restarts
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    Duration::from_millis(nanos as u64 % max_millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use errors::*;
+    use config::RestartConfig;
+    use super::*;
+
+    fn no_jitter(_: Duration) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn policy(max_retries: u32) -> RestartPolicy {
+        let config = RestartConfig {
+            initial_delay_ms: Some(1000),
+            max_delay_ms: Some(5000),
+            max_retries: Some(max_retries),
+            reset_after_secs: Some(60),
+        };
+        RestartPolicy { jitter: no_jitter, ..RestartPolicy::new(Some(&config)) }
+    }
+
+    #[test]
+    fn delay_doubles() -> Result<()> {
+        let mut policy = policy(10);
+        assert_eq!(policy.failed(Duration::from_secs(1))?, Duration::from_secs(1));
+        assert_eq!(policy.failed(Duration::from_secs(1))?, Duration::from_secs(2));
+        assert_eq!(policy.failed(Duration::from_secs(1))?, Duration::from_secs(4));
+        assert_eq!(policy.failures(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn delay_is_capped() -> Result<()> {
+        let mut policy = policy(100);
+        for _ in 0..3 {
+            policy.failed(Duration::from_secs(1))?;
+        }
+        assert_eq!(policy.failed(Duration::from_secs(1))?, Duration::from_secs(5));
+        // the factor overflows long before the retries run out
+        for _ in 0..60 {
+            assert_eq!(policy.failed(Duration::from_secs(1))?, Duration::from_secs(5));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn long_run_resets_failures() -> Result<()> {
+        let mut policy = policy(3);
+        policy.failed(Duration::from_secs(1))?;
+        policy.failed(Duration::from_secs(59))?;
+        assert_eq!(policy.failures(), 2);
+        assert_eq!(policy.failed(Duration::from_secs(60))?, Duration::from_secs(1));
+        assert_eq!(policy.failures(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn give_up_after_max_retries() -> Result<()> {
+        let mut policy = policy(2);
+        policy.failed(Duration::from_secs(1))?;
+        policy.failed(Duration::from_secs(1))?;
+        let err = match policy.failed(Duration::from_secs(1)) {
+            Ok(delay) => bail!("expected to give up, got {:?}", delay),
+            Err(err) => err,
+        };
+        match *err.kind() {
+            ErrorKind::RestartLimitReached(2) => {},
+            ref kind => bail!("expected to give up, got {}", kind),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn jitter_is_added() -> Result<()> {
+        fn max_jitter(max: Duration) -> Duration {
+            max
+        }
+        let mut policy = RestartPolicy { jitter: max_jitter, ..policy(10) };
+        assert_eq!(policy.failed(Duration::from_secs(1))?, Duration::from_millis(1500));
+        assert!(jitter(Duration::from_millis(10)) < Duration::from_millis(10));
+        assert_eq!(jitter(Duration::from_secs(0)), Duration::from_secs(0));
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt::Write;
+
+/// Number of unchanged lines shown around each change in unified diffs.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change<'a> {
+    Equal(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Computes a minimal diff between two token sequences using their longest common subsequence.
+pub fn diff<'a>(from: &[&'a str], to: &[&'a str]) -> Vec<Change<'a>> {
+    // lcs[i][j] is the length of the LCS of from[i..] and to[j..]
+    let mut lcs = vec![vec![0u32; to.len() + 1]; from.len() + 1];
+    for i in (0..from.len()).rev() {
+        for j in (0..to.len()).rev() {
+            lcs[i][j] = if from[i] == to[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                ::std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+    let mut res = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < from.len() && j < to.len() {
+        if from[i] == to[j] {
+            res.push(Change::Equal(from[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            res.push(Change::Removed(from[i]));
+            i += 1;
+        } else {
+            res.push(Change::Added(to[j]));
+            j += 1;
+        }
+    }
+    res.extend(from[i..].iter().map(|s| Change::Removed(s)));
+    res.extend(to[j..].iter().map(|s| Change::Added(s)));
+    res
+}
+
+/// Renders a unified diff between two texts.
+///
+/// Multi-line texts are compared line by line. If both texts are a single line,
+/// they are compared word by word with one word per diff line instead.
+pub fn unified(from: &str, to: &str) -> String {
+    let (from_tokens, to_tokens): (Vec<_>, Vec<_>) = if from.contains('\n') || to.contains('\n') {
+        (from.lines().collect(), to.lines().collect())
+    } else {
+        (from.split_whitespace().collect(), to.split_whitespace().collect())
+    };
+    let changes = diff(&from_tokens, &to_tokens);
+
+    let mut res = String::new();
+    res += "--- before\n";
+    res += "+++ after\n";
+    let changed: Vec<_> = changes.iter().enumerate()
+        .filter(|&(_, c)| match *c { Change::Equal(_) => false, _ => true })
+        .map(|(i, _)| i)
+        .collect();
+    let mut pos = 0;
+    while pos < changed.len() {
+        // merge all changes whose contexts overlap into a single hunk
+        let mut last = pos;
+        while last + 1 < changed.len() && changed[last + 1] - changed[last] <= 2 * CONTEXT {
+            last += 1;
+        }
+        let start = changed[pos].saturating_sub(CONTEXT);
+        let end = ::std::cmp::min(changes.len(), changed[last] + CONTEXT + 1);
+        let count = |range: &[Change], old: bool| range.iter().filter(|c| match **c {
+            Change::Equal(_) => true,
+            Change::Removed(_) => old,
+            Change::Added(_) => !old,
+        }).count();
+        let (old_before, new_before) = (count(&changes[..start], true), count(&changes[..start], false));
+        let (old_len, new_len) = (count(&changes[start..end], true), count(&changes[start..end], false));
+        writeln!(res, "@@ -{},{} +{},{} @@",
+            if old_len == 0 { old_before } else { old_before + 1 }, old_len,
+            if new_len == 0 { new_before } else { new_before + 1 }, new_len).unwrap();
+        for change in &changes[start..end] {
+            match *change {
+                Change::Equal(s) => writeln!(res, " {}", s).unwrap(),
+                Change::Removed(s) => writeln!(res, "-{}", s).unwrap(),
+                Change::Added(s) => writeln!(res, "+{}", s).unwrap(),
+            }
+        }
+        pos = last + 1;
+    }
+    res
+}
+
+/// Renders a word-level diff of two texts for inline display,
+/// with removed words struck through and inserted words in bold.
+///
+/// Markdown within the texts is escaped, so it can't break or fake the markup of the diff.
+pub fn inline(from: &str, to: &str) -> String {
+    let from_tokens = words(from);
+    let to_tokens = words(to);
+    let changes = diff(&from_tokens, &to_tokens);
+
+    let mut res = String::new();
+    let mut removed = String::new();
+    let mut added = String::new();
+    for change in changes {
+        match change {
+            Change::Removed(s) => removed += s,
+            Change::Added(s) => added += s,
+            Change::Equal(s) => {
+                push_run(&mut res, &removed, "~~");
+                push_run(&mut res, &added, "**");
+                removed.clear();
+                added.clear();
+                res += &escape(s);
+            }
+        }
+    }
+    push_run(&mut res, &removed, "~~");
+    push_run(&mut res, &added, "**");
+    res
+}
+
+/// Appends a run of changed tokens wrapped in the given markdown delimiter.
+/// Surrounding whitespace is kept outside of the delimiters, as markdown wouldn't
+/// render them otherwise.
+fn push_run(res: &mut String, run: &str, delimiter: &str) {
+    let trimmed = run.trim();
+    if trimmed.is_empty() {
+        // removed whitespace is dropped, added whitespace is kept as is
+        if delimiter == "**" {
+            *res += run;
+        }
+        return;
+    }
+    let start = run.find(trimmed).unwrap();
+    *res += &run[..start];
+    *res += delimiter;
+    *res += &escape(trimmed);
+    *res += delimiter;
+    *res += &run[start + trimmed.len()..];
+}
+
+/// Escapes the characters discord renders as markdown with a backslash.
+fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\*_~`|".contains(c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Splits a text into alternating runs of words and whitespace.
+fn words(text: &str) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    let mut last_whitespace = None;
+    for (i, c) in text.char_indices() {
+        let whitespace = c.is_whitespace();
+        if last_whitespace.is_some() && last_whitespace != Some(whitespace) {
+            res.push(&text[start..i]);
+            start = i;
+        }
+        last_whitespace = Some(whitespace);
+    }
+    if start < text.len() {
+        res.push(&text[start..]);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::*;
+    use super::{diff, inline, unified, Change};
+
+    #[test]
+    fn diff_is_minimal() -> Result<()> {
+        let changes = diff(&["a", "b", "c", "d"], &["a", "c", "d", "e"]);
+        assert_eq!(changes, vec![Change::Equal("a"), Change::Removed("b"), Change::Equal("c"),
+            Change::Equal("d"), Change::Added("e")]);
+        // the classic example, whose longest common subsequences have a length of 4
+        let from: Vec<_> = "ABCBDAB".split("").filter(|s| !s.is_empty()).collect();
+        let to: Vec<_> = "BDCABA".split("").filter(|s| !s.is_empty()).collect();
+        let changes = diff(&from, &to);
+        let equal = changes.iter().filter(|c| match **c { Change::Equal(_) => true, _ => false }).count();
+        assert_eq!(equal, 4);
+        assert_eq!(changes.len(), from.len() + to.len() - equal);
+        // both sides can be rebuilt from the changes
+        let old: Vec<_> = changes.iter().filter_map(|c| match *c {
+            Change::Equal(s) | Change::Removed(s) => Some(s),
+            Change::Added(_) => None,
+        }).collect();
+        let new: Vec<_> = changes.iter().filter_map(|c| match *c {
+            Change::Equal(s) | Change::Added(s) => Some(s),
+            Change::Removed(_) => None,
+        }).collect();
+        assert_eq!(old, from);
+        assert_eq!(new, to);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_empty() -> Result<()> {
+        assert_eq!(diff(&[], &[]), vec![]);
+        assert_eq!(diff(&[], &["a"]), vec![Change::Added("a")]);
+        assert_eq!(diff(&["a"], &[]), vec![Change::Removed("a")]);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_identical() -> Result<()> {
+        assert_eq!(diff(&["a", "b"], &["a", "b"]), vec![Change::Equal("a"), Change::Equal("b")]);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_words() -> Result<()> {
+        assert_eq!(inline("hello world", "hello big world"), "hello **big** world");
+        assert_eq!(inline("hello big world", "hello world"), "hello ~~big~~ world");
+        assert_eq!(inline("a b c", "a x c"), "a ~~b~~**x** c");
+        assert_eq!(inline("same", "same"), "same");
+        assert_eq!(inline("", "new"), "**new**");
+        Ok(())
+    }
+
+    #[test]
+    fn inline_escapes_markdown() -> Result<()> {
+        assert_eq!(inline("use *this*", "use _that_"), "use ~~\\*this\\*~~**\\_that\\_**");
+        assert_eq!(inline("a*b `c`", "a*b ~~d~~"), "a\\*b ~~\\`c\\`~~**\\~\\~d\\~\\~**");
+        assert_eq!(inline("x", "x || y"), "x **\\|\\| y**");
+        Ok(())
+    }
+
+    #[test]
+    fn unified_words() -> Result<()> {
+        assert_eq!(unified("a b c", "a x c"), "--- before\n+++ after\n@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n");
+        assert_eq!(unified("a b", "a b"), "--- before\n+++ after\n");
+        Ok(())
+    }
+
+    #[test]
+    fn unified_lines() -> Result<()> {
+        let from = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let to = "0\n2\n3\n4\n5\n6\n7\n8\n9\n11\n";
+        // changes more than twice the context apart get their own hunks
+        assert_eq!(unified(from, to), "--- before\n+++ after\n\
+            @@ -1,4 +1,4 @@\n-1\n+0\n 2\n 3\n 4\n\
+            @@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+11\n");
+        assert_eq!(unified("a\nb\n", "a\nc\nb\n"), "--- before\n+++ after\n@@ -1,2 +1,3 @@\n a\n+c\n b\n");
+        assert_eq!(unified("", "a\n"), "--- before\n+++ after\n@@ -0,0 +1,1 @@\n+a\n");
+        Ok(())
+    }
+}